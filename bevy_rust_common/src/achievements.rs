@@ -0,0 +1,212 @@
+use std::{collections::HashSet, fs};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{asset_path, menu::not_paused};
+
+const DEFINITIONS_FILE: &str = "achievements.ron";
+const TOAST_SECONDS: f32 = 3.0;
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum Condition {
+    /// Unlocks once a `Progress` event for `stat` reports at least `value`.
+    Reached { stat: String, value: u32 },
+    /// Unlocks on the first `Trigger` event with this name.
+    Triggered(String),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AchievementDef {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+/// Sent by the games to report what happened; the plugin decides what unlocks.
+pub enum AchievementEvent {
+    Progress { stat: &'static str, value: u32 },
+    Trigger(&'static str),
+}
+
+// Resource
+#[derive(Serialize, Deserialize, Default)]
+struct UnlockedAchievements {
+    #[serde(skip)]
+    path: String,
+    unlocked: HashSet<String>,
+}
+
+impl UnlockedAchievements {
+    fn load(game: &str) -> Self {
+        let path = format!("{}_achievements.ron", game);
+        let mut unlocked: Self = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| ron::from_str(&content).ok())
+            .unwrap_or_default();
+        unlocked.path = path;
+        unlocked
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&self.path, content).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            warn!("could not save achievements: {}", e);
+        }
+    }
+}
+
+// Resource
+struct Achievements(Vec<AchievementDef>);
+
+// Resource
+#[derive(Default)]
+struct ToastQueue(Vec<AchievementDef>);
+
+// Component
+struct Toast(Timer);
+
+/// Loads the achievement definitions from `assets/achievements.ron`,
+/// unlocks them from `AchievementEvent`s and pops up a toast for each unlock.
+///
+/// The game has to spawn a `UiCameraBundle` for the toasts to show.
+pub struct AchievementsPlugin {
+    pub game: &'static str,
+    /// Path of the toast font, relative to the assets folder.
+    pub font: &'static str,
+}
+
+struct ToastFont(&'static str);
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let definitions = fs::read_to_string(asset_path(DEFINITIONS_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str(&content).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", DEFINITIONS_FILE, e);
+                Vec::new()
+            });
+
+        app.add_event::<AchievementEvent>()
+            .insert_resource(Achievements(definitions))
+            .insert_resource(UnlockedAchievements::load(self.game))
+            .insert_resource(ToastQueue::default())
+            .insert_resource(ToastFont(self.font))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(unlock_achievements.system())
+                    .with_system(show_toast.system())
+                    .with_system(expire_toast.system()),
+            );
+    }
+}
+
+fn unlock_achievements(
+    mut events: EventReader<AchievementEvent>,
+    achievements: Res<Achievements>,
+    mut unlocked: ResMut<UnlockedAchievements>,
+    mut toasts: ResMut<ToastQueue>,
+) {
+    let mut changed = false;
+    for event in events.iter() {
+        for def in achievements.0.iter() {
+            if unlocked.unlocked.contains(&def.id) {
+                continue;
+            }
+
+            let met = match (&def.condition, event) {
+                (
+                    Condition::Reached { stat, value },
+                    AchievementEvent::Progress { stat: s, value: v },
+                ) => stat == s && v >= value,
+                (Condition::Triggered(name), AchievementEvent::Trigger(n)) => name == n,
+                _ => false,
+            };
+
+            if met {
+                info!("achievement unlocked: {}", def.name);
+                unlocked.unlocked.insert(def.id.clone());
+                toasts.0.push(def.clone());
+                changed = true;
+            }
+        }
+    }
+
+    if changed {
+        unlocked.save();
+    }
+}
+
+fn show_toast(
+    mut commands: Commands,
+    mut toasts: ResMut<ToastQueue>,
+    font: Res<ToastFont>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    shown: Query<&Toast>,
+) {
+    // one toast at a time, in unlock order
+    if toasts.0.is_empty() || shown.iter().next().is_some() {
+        return;
+    }
+    let def = toasts.0.remove(0);
+    let font = asset_server.load(font.0);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(8.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.7).into()),
+            ..Default::default()
+        })
+        .insert(Toast(Timer::from_seconds(TOAST_SECONDS, false)))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("Achievement unlocked: {}", def.name),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 18.0,
+                        color: Color::GOLD,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    def.description,
+                    TextStyle {
+                        font,
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn expire_toast(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut Toast)>) {
+    for (entity, mut toast) in query.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
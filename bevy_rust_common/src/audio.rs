@@ -0,0 +1,347 @@
+use std::{collections::HashMap, fs, io::Cursor};
+
+use bevy::prelude::*;
+use rodio::{source::Buffered, Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_path,
+    menu::{not_paused, OpenMenus, Paused, AUDIO_KEY, MENU_STAGE, MUTE_KEY},
+};
+
+const VOLUME_STEP: f32 = 0.1;
+const SLIDER_WIDTH: usize = 10;
+
+type Sound = Buffered<Decoder<Cursor<Vec<u8>>>>;
+
+/// Sent by the games to play the sound effect with that name.
+pub struct PlaySound(pub &'static str);
+
+/// Volumes and mute switch, saved to `<game>_settings.ron`.
+#[derive(Serialize, Deserialize)]
+pub struct AudioSettings {
+    #[serde(skip)]
+    path: String,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    pub muted: bool,
+}
+
+impl AudioSettings {
+    fn load(game: &str) -> Self {
+        let path = format!("{}_settings.ron", game);
+        let mut settings: Self = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| ron::from_str(&content).ok())
+            .unwrap_or_else(|| Self {
+                path: String::new(),
+                music_volume: 0.5,
+                effects_volume: 0.8,
+                muted: false,
+            });
+        settings.path = path;
+        settings
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&self.path, content).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            warn!("could not save settings: {}", e);
+        }
+    }
+
+    fn volume(&self, volume: f32) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            volume
+        }
+    }
+}
+
+// Non-send resource: the output stream has to stay on the thread it was
+// opened on
+#[derive(Default)]
+struct AudioOutput {
+    // None without an audio device; dropping the stream stops all sound
+    stream: Option<(OutputStream, OutputStreamHandle)>,
+    music: Option<Sink>,
+}
+
+// Resource
+struct Sounds(HashMap<&'static str, Sound>);
+
+// Resource
+#[derive(Default)]
+struct AudioMenu {
+    selected: usize,
+}
+
+// Component
+struct AudioMenuRoot;
+
+struct MenuFont(&'static str);
+
+fn load_sound(path: &str) -> Result<Sound, String> {
+    let bytes = fs::read(asset_path(path)).map_err(|e| format!("{}: {}", path, e))?;
+    let decoder = Decoder::new(Cursor::new(bytes)).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(decoder.buffered())
+}
+
+/// Plays `PlaySound` effects from `assets/sounds/<name>.wav` and loops
+/// `assets/sounds/music.wav`. M mutes, F2 opens the volume sliders.
+///
+/// Without an audio device the game stays silent. Use it instead of
+/// bevy's `AudioPlugin`, which panics then. Needs the `InputMapPlugin`,
+/// whose pause screen the sliders open from.
+pub struct SoundPlugin {
+    pub game: &'static str,
+    /// Names of the effects the game plays.
+    pub sounds: &'static [&'static str],
+    /// Path of the menu font, relative to the assets folder.
+    pub font: &'static str,
+}
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let settings = AudioSettings::load(self.game);
+
+        let mut sounds = HashMap::new();
+        for name in self.sounds {
+            match load_sound(&format!("sounds/{}.wav", name)) {
+                Ok(sound) => {
+                    sounds.insert(*name, sound);
+                }
+                Err(e) => warn!("could not load sound {}", e),
+            }
+        }
+
+        let mut output = AudioOutput::default();
+        match OutputStream::try_default() {
+            Ok((stream, handle)) => {
+                output.music = load_sound("sounds/music.wav")
+                    .and_then(|music| {
+                        let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
+                        sink.set_volume(settings.volume(settings.music_volume));
+                        sink.append(music.repeat_infinite());
+                        Ok(sink)
+                    })
+                    .map_err(|e| warn!("could not play music: {}", e))
+                    .ok();
+                output.stream = Some((stream, handle));
+            }
+            Err(e) => warn!("no audio device, sound is off: {}", e),
+        }
+
+        app.add_event::<PlaySound>()
+            .insert_non_send_resource(output)
+            .insert_resource(settings)
+            .insert_resource(Sounds(sounds))
+            .insert_resource(AudioMenu::default())
+            .insert_resource(MenuFont(self.font))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(play_sounds.system()),
+            )
+            .add_system_to_stage(
+                MENU_STAGE,
+                audio_menu
+                    .system()
+                    .label("audio_menu")
+                    .after("toggle_pause")
+                    .after("rebind_controls"),
+            )
+            .add_system_to_stage(MENU_STAGE, apply_settings.system().after("audio_menu"))
+            .add_system_to_stage(MENU_STAGE, draw_audio_menu.system().after("audio_menu"));
+    }
+}
+
+fn play_sounds(
+    mut events: EventReader<PlaySound>,
+    output: NonSend<AudioOutput>,
+    sounds: Res<Sounds>,
+    settings: Res<AudioSettings>,
+) {
+    let handle = match &output.stream {
+        Some((_, handle)) => handle,
+        None => return,
+    };
+
+    for PlaySound(name) in events.iter() {
+        let sound = match sounds.0.get(name) {
+            Some(sound) => sound.clone(),
+            None => continue,
+        };
+        match Sink::try_new(handle) {
+            Ok(sink) => {
+                sink.set_volume(settings.volume(settings.effects_volume));
+                sink.append(sound);
+                sink.detach();
+            }
+            Err(e) => warn!("could not play {}: {}", name, e),
+        }
+    }
+}
+
+fn apply_settings(output: NonSend<AudioOutput>, settings: Res<AudioSettings>) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Some(music) = &output.music {
+        music.set_volume(settings.volume(settings.music_volume));
+    }
+}
+
+fn audio_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut open: ResMut<OpenMenus>,
+    mut menu: ResMut<AudioMenu>,
+    mut settings: ResMut<AudioSettings>,
+    mut paused: ResMut<Paused>,
+) {
+    if open.controls {
+        // keys belong to the controls screen
+        return;
+    }
+
+    let mut changed = false;
+    if keyboard_input.just_pressed(MUTE_KEY) {
+        settings.muted = !settings.muted;
+        changed = true;
+    }
+
+    if !open.audio {
+        if keyboard_input.just_pressed(AUDIO_KEY) {
+            open.audio = true;
+            *menu = AudioMenu::default();
+            paused.0 = true;
+        }
+    } else if keyboard_input.just_pressed(AUDIO_KEY) || keyboard_input.just_pressed(KeyCode::Escape)
+    {
+        // back to the pause screen
+        open.audio = false;
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        menu.selected = (menu.selected + 2) % 3;
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % 3;
+    } else {
+        let step = if keyboard_input.just_pressed(KeyCode::Right) {
+            VOLUME_STEP
+        } else if keyboard_input.just_pressed(KeyCode::Left) {
+            -VOLUME_STEP
+        } else {
+            0.0
+        };
+        let toggle = keyboard_input.just_pressed(KeyCode::Return);
+
+        match menu.selected {
+            0 if step != 0.0 => {
+                settings.music_volume = (settings.music_volume + step).clamp(0.0, 1.0);
+                changed = true;
+            }
+            1 if step != 0.0 => {
+                settings.effects_volume = (settings.effects_volume + step).clamp(0.0, 1.0);
+                changed = true;
+            }
+            2 if step != 0.0 || toggle => {
+                settings.muted = !settings.muted;
+                changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    if changed {
+        settings.save();
+    }
+}
+
+fn slider(volume: f32) -> String {
+    let filled = (volume * SLIDER_WIDTH as f32).round() as usize;
+    format!(
+        "[{}{}] {:>3}%",
+        "#".repeat(filled),
+        "-".repeat(SLIDER_WIDTH - filled),
+        (volume * 100.0).round()
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_audio_menu(
+    mut commands: Commands,
+    open: Res<OpenMenus>,
+    menu: Res<AudioMenu>,
+    settings: Res<AudioSettings>,
+    font: Res<MenuFont>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    shown: Query<Entity, With<AudioMenuRoot>>,
+) {
+    if !(open.is_changed() || menu.is_changed() || settings.is_changed()) {
+        return;
+    }
+    for entity in shown.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !open.audio {
+        return;
+    }
+
+    let font = asset_server.load(font.0);
+    let style = |font_size, color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+
+    let rows = [
+        format!("Music:   {}", slider(settings.music_volume)),
+        format!("Effects: {}", slider(settings.effects_volume)),
+        format!("Mute:    {}", if settings.muted { "on" } else { "off" }),
+    ];
+    let mut sections = vec![TextSection {
+        value: "Audio\n\n".to_string(),
+        style: style(24.0, Color::WHITE),
+    }];
+    for (i, row) in rows.iter().enumerate() {
+        let selected = i == menu.selected;
+        sections.push(TextSection {
+            value: format!("{}{}\n", if selected { "> " } else { "  " }, row),
+            style: style(16.0, if selected { Color::GOLD } else { Color::WHITE }),
+        });
+    }
+    sections.push(TextSection {
+        value: "\nLeft/Right: adjust   M: mute   Esc: back".to_string(),
+        style: style(14.0, Color::GRAY),
+    });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(40.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(12.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.8).into()),
+            ..Default::default()
+        })
+        .insert(AudioMenuRoot)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        });
+}
@@ -0,0 +1,186 @@
+use std::{
+    cmp::Reverse,
+    fmt, fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hash::Fnv1a;
+
+const DEFAULT_PLAYER: &str = "player";
+
+/// A calendar day in UTC.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today_utc() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self::from_days_since_epoch((secs / 86_400) as i64)
+    }
+
+    // Howard Hinnant's `civil_from_days`
+    fn from_days_since_epoch(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+
+        Self { year, month, day }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Settings of a daily challenge run, taken from the command line.
+///
+/// Everything random in a daily run must be derived from `seed`, so every
+/// player gets the same game on the same day.
+#[derive(Clone)]
+pub struct DailyChallenge {
+    pub game: &'static str,
+    pub date: Date,
+    pub seed: u64,
+    pub player: String,
+    pub one_attempt: bool,
+}
+
+impl DailyChallenge {
+    /// Returns the challenge for today if `--daily` was passed.
+    ///
+    /// `--one-attempt` limits every player to a single run per day, so it is
+    /// an error if the player already played today. `--name <player>` sets
+    /// the name used on the leaderboard.
+    pub fn from_args(game: &'static str) -> Result<Option<Self>, String> {
+        let args: Vec<String> = std::env::args().collect();
+        if !args.iter().any(|arg| arg == "--daily") {
+            return Ok(None);
+        }
+
+        let player = args
+            .iter()
+            .position(|arg| arg == "--name")
+            .and_then(|i| args.get(i + 1).cloned())
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .unwrap_or_else(|| DEFAULT_PLAYER.to_string());
+        let date = Date::today_utc();
+        let challenge = Self {
+            game,
+            date,
+            seed: seed_for(game, &date),
+            player,
+            one_attempt: args.iter().any(|arg| arg == "--one-attempt"),
+        };
+
+        if challenge.one_attempt && challenge.leaderboard().has_played(&challenge.player) {
+            return Err(format!(
+                "{} already played the daily challenge of {}",
+                challenge.player, challenge.date
+            ));
+        }
+        Ok(Some(challenge))
+    }
+
+    pub fn leaderboard(&self) -> DailyLeaderboard {
+        DailyLeaderboard::load(self.game, &self.date)
+    }
+}
+
+/// Hash of the game name and date, so every copy of the game agrees on the
+/// seed.
+fn seed_for(game: &str, date: &Date) -> u64 {
+    let mut hash = Fnv1a::default();
+    hash.write(format!("{}/{}", game, date).as_bytes());
+    hash.finish()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DailyEntry {
+    pub player: String,
+    pub score: u32,
+}
+
+/// Best result per player for one game and one day, stored in its own file.
+#[derive(Serialize, Deserialize, Default)]
+pub struct DailyLeaderboard {
+    #[serde(skip)]
+    path: String,
+    entries: Vec<DailyEntry>,
+}
+
+impl DailyLeaderboard {
+    pub fn load(game: &str, date: &Date) -> Self {
+        let path = format!("{}_daily_{}.ron", game, date);
+        let mut leaderboard: Self = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| ron::from_str(&content).ok())
+            .unwrap_or_default();
+        leaderboard.path = path;
+        leaderboard
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())?;
+        fs::write(&self.path, content).map_err(|e| e.to_string())
+    }
+
+    pub fn entries(&self) -> &[DailyEntry] {
+        &self.entries
+    }
+
+    pub fn has_played(&self, player: &str) -> bool {
+        self.entries.iter().any(|e| e.player == player)
+    }
+
+    /// Registers a run as soon as it starts, so quitting early still
+    /// counts as the day's attempt.
+    pub fn begin_attempt(&mut self, player: &str) -> Result<(), String> {
+        if self.has_played(player) {
+            return Ok(());
+        }
+        self.entries.push(DailyEntry {
+            player: player.to_string(),
+            score: 0,
+        });
+        self.save()
+    }
+
+    /// Keeps the better of `score` and the player's previous result.
+    /// Returns the player's rank (starting at 1) after the update.
+    pub fn submit(&mut self, player: &str, score: u32) -> Result<usize, String> {
+        match self.entries.iter_mut().find(|e| e.player == player) {
+            Some(entry) => entry.score = entry.score.max(score),
+            None => self.entries.push(DailyEntry {
+                player: player.to_string(),
+                score,
+            }),
+        }
+        self.entries.sort_by_key(|e| Reverse(e.score));
+        self.save()?;
+
+        Ok(self
+            .entries
+            .iter()
+            .position(|e| e.player == player)
+            .map_or(self.entries.len(), |i| i + 1))
+    }
+}
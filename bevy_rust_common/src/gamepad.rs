@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+
+/// Stick deflection below this is treated as centered.
+const STICK_DEADZONE: f32 = 0.25;
+
+/// Connected gamepads by player slot, in connection order.
+///
+/// A pad that is unplugged frees its slot and the next pad plugged in
+/// takes it over, so players keep their slot across hot-plugging.
+#[derive(Default)]
+pub struct PlayerGamepads(Vec<Option<Gamepad>>);
+
+impl PlayerGamepads {
+    pub fn get(&self, player: usize) -> Option<Gamepad> {
+        self.0.get(player).copied().flatten()
+    }
+
+    /// Player slot and pad of every connected gamepad.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Gamepad)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(player, pad)| pad.map(|pad| (player, pad)))
+    }
+
+    fn connect(&mut self, pad: Gamepad) -> usize {
+        if let Some(player) = self.0.iter().position(|slot| *slot == Some(pad)) {
+            return player;
+        }
+        match self.0.iter().position(Option::is_none) {
+            Some(player) => {
+                self.0[player] = Some(pad);
+                player
+            }
+            None => {
+                self.0.push(Some(pad));
+                self.0.len() - 1
+            }
+        }
+    }
+
+    fn disconnect(&mut self, pad: Gamepad) -> Option<usize> {
+        let player = self.0.iter().position(|slot| *slot == Some(pad))?;
+        self.0[player] = None;
+        Some(player)
+    }
+}
+
+/// Keeps `PlayerGamepads` up to date as pads are plugged in and out.
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(PlayerGamepads::default())
+            .add_system_to_stage(CoreStage::PreUpdate, assign_gamepads.system());
+    }
+}
+
+fn assign_gamepads(mut events: EventReader<GamepadEvent>, mut gamepads: ResMut<PlayerGamepads>) {
+    for GamepadEvent(pad, event_type) in events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                let player = gamepads.connect(*pad);
+                info!("gamepad {} connected as player {}", pad.0, player + 1);
+            }
+            GamepadEventType::Disconnected => {
+                if let Some(player) = gamepads.disconnect(*pad) {
+                    info!("gamepad {} of player {} disconnected", pad.0, player + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Direction held on `pad`, each component in -1..=1 with up being +y.
+///
+/// The d-pad wins over the left stick. Some pads report the d-pad as
+/// buttons and others as axes, so both are read.
+pub fn direction(pad: Gamepad, buttons: &Input<GamepadButton>, axes: &Axis<GamepadAxis>) -> Vec2 {
+    let button = |button_type| buttons.pressed(GamepadButton(pad, button_type)) as i32 as f32;
+    let axis = |axis_type| axes.get(GamepadAxis(pad, axis_type)).unwrap_or(0.0);
+
+    let dpad_buttons = Vec2::new(
+        button(GamepadButtonType::DPadRight) - button(GamepadButtonType::DPadLeft),
+        button(GamepadButtonType::DPadUp) - button(GamepadButtonType::DPadDown),
+    );
+    if dpad_buttons != Vec2::ZERO {
+        return dpad_buttons;
+    }
+
+    let dpad_axes = Vec2::new(
+        axis(GamepadAxisType::DPadX).round(),
+        axis(GamepadAxisType::DPadY).round(),
+    );
+    if dpad_axes != Vec2::ZERO {
+        return dpad_axes;
+    }
+
+    let stick = Vec2::new(
+        axis(GamepadAxisType::LeftStickX),
+        axis(GamepadAxisType::LeftStickY),
+    );
+    if stick.length() < STICK_DEADZONE {
+        Vec2::ZERO
+    } else {
+        stick.clamp(Vec2::splat(-1.0), Vec2::ONE)
+    }
+}
+
+/// Whether any of `button_types` is held on `pad`.
+pub fn pressed(
+    pad: Gamepad,
+    buttons: &Input<GamepadButton>,
+    button_types: &[GamepadButtonType],
+) -> bool {
+    button_types
+        .iter()
+        .any(|button_type| buttons.pressed(GamepadButton(pad, *button_type)))
+}
@@ -0,0 +1,455 @@
+use std::{collections::HashMap, fs};
+
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gamepad::{self, PlayerGamepads},
+    menu::{
+        OpenMenus, Paused, CONTROLS_BUTTON, CONTROLS_KEY, MENU_STAGE, RESERVED_BUTTONS,
+        RESERVED_KEYS,
+    },
+};
+
+const CANCEL_KEY: KeyCode = KeyCode::Escape;
+const CONFIRM_KEY: KeyCode = KeyCode::Return;
+const CONFIRM_BUTTON: GamepadButtonType = GamepadButtonType::South;
+
+/// Stick deflection past which a stick direction counts as pressed.
+const STICK_PRESS: f32 = 0.5;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Fire,
+    /// Held to move slowly, for precise dodging.
+    Focus,
+    Rewind,
+    Pause,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::Fire => "Fire",
+            Action::Focus => "Focus",
+            Action::Rewind => "Rewind",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    /// Button type on player one's gamepad.
+    Button(GamepadButtonType),
+}
+
+impl Binding {
+    fn name(self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Button(button) => format!("Pad {:?}", button),
+        }
+    }
+
+    /// Whether a menu listens to this key or button whatever the bindings.
+    fn reserved(self) -> bool {
+        match self {
+            Binding::Key(key) => RESERVED_KEYS.contains(&key),
+            Binding::Button(button) => RESERVED_BUTTONS.contains(&button),
+        }
+    }
+
+    fn same_device(self, other: Binding) -> bool {
+        matches!(
+            (self, other),
+            (Binding::Key(_), Binding::Key(_)) | (Binding::Button(_), Binding::Button(_))
+        )
+    }
+}
+
+/// Bindings of every action the game uses, in the order they are listed on
+/// the rebinding screen. Saved to `<game>_bindings.ron`.
+pub struct InputBindings {
+    path: String,
+    bindings: Vec<(Action, Vec<Binding>)>,
+}
+
+impl InputBindings {
+    /// Loads the saved bindings, falling back to `defaults` for every action
+    /// missing from the file. Actions not in `defaults` are dropped.
+    fn load(game: &str, defaults: &[(Action, Vec<Binding>)]) -> Self {
+        let path = format!("{}_bindings.ron", game);
+        let saved: Vec<(Action, Vec<Binding>)> = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| ron::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let bindings = defaults
+            .iter()
+            .map(|(action, default)| {
+                let bindings = saved
+                    .iter()
+                    .find(|(a, _)| a == action)
+                    .map_or_else(|| default.clone(), |(_, bindings)| bindings.clone());
+                (*action, bindings)
+            })
+            .collect();
+        Self { path, bindings }
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.bindings, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&self.path, content).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            warn!("could not save bindings: {}", e);
+        }
+    }
+
+    /// Action other than `action` that `binding` is already bound to.
+    fn conflict(&self, action: Action, binding: Binding) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(a, bindings)| *a != action && bindings.contains(&binding))
+            .map(|(a, _)| *a)
+    }
+
+    /// Replaces the bindings of `action` on the device of `binding`.
+    fn rebind(&mut self, action: Action, binding: Binding) {
+        if let Some((_, bindings)) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            bindings.retain(|b| !b.same_device(binding));
+            bindings.push(binding);
+        }
+    }
+}
+
+/// What the player is doing this frame, in terms of actions.
+///
+/// Systems should read this rather than the keyboard or gamepads.
+#[derive(Default)]
+pub struct ActionState {
+    input: Input<Action>,
+    values: HashMap<Action, f32>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.input.pressed(action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.input.just_pressed(action)
+    }
+
+    /// How far the action is held, in 0..=1. Keys and buttons are either 0
+    /// or 1, the stick gives anything in between.
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    /// Holds `action` by `value` in 0..=1 until set again, as the input
+    /// does every frame. It counts as pressed from `STICK_PRESS` on.
+    pub fn set(&mut self, action: Action, value: f32) {
+        self.values.insert(action, value);
+        if value >= STICK_PRESS {
+            self.input.press(action);
+        } else {
+            self.input.release(action);
+        }
+    }
+}
+
+// Resource
+#[derive(Default)]
+struct RebindMenu {
+    selected: usize,
+    capturing: bool,
+    message: Option<String>,
+}
+
+// Component
+struct MenuRoot;
+
+struct MenuFont(&'static str);
+
+/// Maps keys and gamepad buttons to `Action`s, pauses on `Action::Pause`
+/// and opens a screen to rebind the actions with F1 or the pad's Select
+/// button. Movement actions can also be held with player one's left stick
+/// or d-pad, which is not rebindable.
+///
+/// The game has to spawn a `UiCameraBundle` for the screens to show.
+pub struct InputMapPlugin {
+    pub game: &'static str,
+    /// Bindings used for actions the player hasn't rebound yet.
+    pub defaults: Vec<(Action, Vec<Binding>)>,
+    /// Path of the menu font, relative to the assets folder.
+    pub font: &'static str,
+}
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(InputBindings::load(self.game, &self.defaults))
+            .insert_resource(ActionState::default())
+            .insert_resource(Paused::default())
+            .insert_resource(OpenMenus::default())
+            .insert_resource(RebindMenu::default())
+            .insert_resource(MenuFont(self.font))
+            .add_stage_after(CoreStage::PreUpdate, MENU_STAGE, SystemStage::parallel())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_actions.system().after(InputSystem),
+            )
+            .add_system_to_stage(MENU_STAGE, toggle_pause.system().label("toggle_pause"))
+            .add_system_to_stage(
+                MENU_STAGE,
+                rebind_controls
+                    .system()
+                    .label("rebind_controls")
+                    .after("toggle_pause"),
+            )
+            .add_system_to_stage(MENU_STAGE, draw_menu.system().after("rebind_controls"));
+    }
+}
+
+fn update_actions(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<PlayerGamepads>,
+    bindings: Res<InputBindings>,
+    mut actions: ResMut<ActionState>,
+) {
+    let pad = gamepads.get(0);
+    let stick = pad.map_or(Vec2::ZERO, |pad| {
+        gamepad::direction(pad, &gamepad_buttons, &gamepad_axes)
+    });
+
+    actions.input.update();
+    for (action, action_bindings) in bindings.bindings.iter() {
+        let bound = action_bindings.iter().any(|binding| match *binding {
+            Binding::Key(key) => keyboard_input.pressed(key),
+            Binding::Button(button_type) => pad.map_or(false, |pad| {
+                gamepad::pressed(pad, &gamepad_buttons, &[button_type])
+            }),
+        });
+        let stick_value = match action {
+            Action::MoveLeft => (-stick.x).max(0.0),
+            Action::MoveRight => stick.x.max(0.0),
+            Action::MoveUp => stick.y.max(0.0),
+            Action::MoveDown => (-stick.y).max(0.0),
+            _ => 0.0,
+        };
+
+        actions.set(*action, if bound { 1.0 } else { stick_value });
+    }
+}
+
+fn toggle_pause(actions: Res<ActionState>, open: Res<OpenMenus>, mut paused: ResMut<Paused>) {
+    if !open.any() && actions.just_pressed(Action::Pause) {
+        paused.0 = !paused.0;
+    }
+}
+
+fn rebind_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<PlayerGamepads>,
+    mut bindings: ResMut<InputBindings>,
+    mut menu: ResMut<RebindMenu>,
+    mut open: ResMut<OpenMenus>,
+    mut paused: ResMut<Paused>,
+) {
+    if open.audio {
+        // keys belong to the audio screen
+        return;
+    }
+
+    let pad = gamepads.get(0);
+    let button = |button_type| {
+        pad.map_or(false, |pad| {
+            gamepad_buttons.just_pressed(GamepadButton(pad, button_type))
+        })
+    };
+
+    if !open.controls {
+        if keyboard_input.just_pressed(CONTROLS_KEY) || button(CONTROLS_BUTTON) {
+            open.controls = true;
+            *menu = RebindMenu::default();
+            paused.0 = true;
+        }
+        return;
+    }
+
+    let (action, _) = bindings.bindings[menu.selected];
+    if menu.capturing {
+        if keyboard_input.just_pressed(CANCEL_KEY) {
+            menu.capturing = false;
+            menu.message = None;
+            return;
+        }
+
+        let pressed = keyboard_input
+            .get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                let pad = pad?;
+                gamepad_buttons
+                    .get_just_pressed()
+                    .find(|button| button.0 == pad)
+                    .map(|button| Binding::Button(button.1))
+            });
+
+        if let Some(binding) = pressed {
+            menu.capturing = false;
+            if binding.reserved() {
+                menu.message = Some(format!("{} is kept for the menus", binding.name()));
+                return;
+            }
+            match bindings.conflict(action, binding) {
+                Some(other) => {
+                    menu.message = Some(format!(
+                        "{} is already bound to {}",
+                        binding.name(),
+                        other.name()
+                    ));
+                }
+                None => {
+                    bindings.rebind(action, binding);
+                    bindings.save();
+                    menu.message = None;
+                }
+            }
+        }
+        return;
+    }
+
+    let count = bindings.bindings.len();
+    if keyboard_input.just_pressed(CONTROLS_KEY)
+        || keyboard_input.just_pressed(CANCEL_KEY)
+        || button(CONTROLS_BUTTON)
+    {
+        // back to the pause screen
+        open.controls = false;
+    } else if keyboard_input.just_pressed(KeyCode::Up) || button(GamepadButtonType::DPadUp) {
+        menu.selected = (menu.selected + count - 1) % count;
+    } else if keyboard_input.just_pressed(KeyCode::Down) || button(GamepadButtonType::DPadDown) {
+        menu.selected = (menu.selected + 1) % count;
+    } else if keyboard_input.just_pressed(CONFIRM_KEY) || button(CONFIRM_BUTTON) {
+        menu.capturing = true;
+        menu.message = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_menu(
+    mut commands: Commands,
+    menu: Res<RebindMenu>,
+    open: Res<OpenMenus>,
+    paused: Res<Paused>,
+    bindings: Res<InputBindings>,
+    font: Res<MenuFont>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    shown: Query<Entity, With<MenuRoot>>,
+) {
+    if !(menu.is_changed() || open.is_changed() || paused.is_changed() || bindings.is_changed()) {
+        return;
+    }
+    for entity in shown.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // the audio screen draws itself
+    if !paused.0 || open.audio {
+        return;
+    }
+
+    let font = asset_server.load(font.0);
+    let style = |font_size, color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+
+    let mut sections = Vec::new();
+    if open.controls {
+        sections.push(("Controls\n\n".to_string(), style(24.0, Color::WHITE)));
+        for (i, (action, action_bindings)) in bindings.bindings.iter().enumerate() {
+            let selected = i == menu.selected;
+            let bound = if selected && menu.capturing {
+                "press a key or button...".to_string()
+            } else {
+                action_bindings
+                    .iter()
+                    .map(|binding| binding.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let color = if selected { Color::GOLD } else { Color::WHITE };
+            sections.push((
+                format!(
+                    "{}{}: {}\n",
+                    if selected { "> " } else { "  " },
+                    action.name(),
+                    bound
+                ),
+                style(16.0, color),
+            ));
+        }
+        if let Some(message) = &menu.message {
+            sections.push((format!("\n{}\n", message), style(16.0, Color::RED)));
+        }
+        sections.push((
+            "\nEnter: rebind   Esc: back".to_string(),
+            style(14.0, Color::GRAY),
+        ));
+    } else {
+        sections.push(("Paused\n\n".to_string(), style(24.0, Color::WHITE)));
+        sections.push((
+            "F1: controls   F2: audio".to_string(),
+            style(14.0, Color::GRAY),
+        ));
+    }
+
+    let sections = sections
+        .into_iter()
+        .map(|(value, style)| TextSection { value, style })
+        .collect();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(40.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(12.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.8).into()),
+            ..Default::default()
+        })
+        .insert(MenuRoot)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        });
+}
@@ -0,0 +1,21 @@
+//! Code shared by the games in the collection.
+
+pub mod achievements;
+pub mod audio;
+pub mod daily;
+pub mod gamepad;
+pub mod hash;
+pub mod input;
+pub mod menu;
+pub mod particles;
+pub mod stats;
+
+use std::path::PathBuf;
+
+use bevy::asset::FileAssetIo;
+
+/// Resolves `relative` inside the game's assets folder the same way the
+/// `AssetServer` does, for data files that are read synchronously.
+pub fn asset_path(relative: &str) -> PathBuf {
+    FileAssetIo::get_root_path().join("assets").join(relative)
+}
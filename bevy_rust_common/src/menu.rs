@@ -0,0 +1,55 @@
+//! State shared by the pause screen and the screens opened from it.
+
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+/// Stage between `PreUpdate` and `Update` that keeps running while paused.
+pub const MENU_STAGE: &str = "menu";
+
+// keys and buttons of the menus, fixed so a bad binding can never lock the
+// player out of them
+pub(crate) const CONTROLS_KEY: KeyCode = KeyCode::F1;
+pub(crate) const CONTROLS_BUTTON: GamepadButtonType = GamepadButtonType::Select;
+pub(crate) const AUDIO_KEY: KeyCode = KeyCode::F2;
+pub(crate) const MUTE_KEY: KeyCode = KeyCode::M;
+pub(crate) const RESERVED_KEYS: [KeyCode; 3] = [CONTROLS_KEY, AUDIO_KEY, MUTE_KEY];
+pub(crate) const RESERVED_BUTTONS: [GamepadButtonType; 1] = [CONTROLS_BUTTON];
+
+/// Whether the game is paused. Systems run on `not_paused` don't run while
+/// it is.
+#[derive(Default)]
+pub struct Paused(pub bool);
+
+// Resource
+/// Screens open on top of the pause screen, which take the keys while open.
+#[derive(Default)]
+pub(crate) struct OpenMenus {
+    pub(crate) controls: bool,
+    pub(crate) audio: bool,
+}
+
+impl OpenMenus {
+    pub(crate) fn any(&self) -> bool {
+        self.controls || self.audio
+    }
+}
+
+/// Run criterion of the games' own systems in `Update`, which stop while
+/// paused. Bevy's systems there, like quitting when the window is closed,
+/// keep running.
+pub fn not_paused(paused: Res<Paused>) -> ShouldRun {
+    if paused.0 {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+/// Holds back another run criterion while paused, as in
+/// `FixedTimestep::step(0.5).chain(unless_paused.system())`.
+pub fn unless_paused(In(should_run): In<ShouldRun>, paused: Res<Paused>) -> ShouldRun {
+    if paused.0 {
+        ShouldRun::No
+    } else {
+        should_run
+    }
+}
@@ -0,0 +1,276 @@
+use std::{collections::HashMap, fs};
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{asset_path, menu::not_paused};
+
+const DEFINITIONS_FILE: &str = "particles.ron";
+/// Colors a particle steps through over its life, each a shared material.
+const COLOR_STEPS: usize = 16;
+
+/// How an emitter spawns particles, loaded by name from `assets/particles.ron`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct EmitterDef {
+    /// Particles spawned at once by a `SpawnParticles` burst.
+    #[serde(default)]
+    pub burst: u32,
+    /// Particles per second of a `ParticleEmitter`.
+    #[serde(default)]
+    pub rate: f32,
+    /// Seconds a particle lives, picked between the two.
+    pub lifetime: (f32, f32),
+    /// Pixels per second, picked between the two.
+    pub speed: (f32, f32),
+    /// Center of the velocity cone, in degrees counterclockwise from +x.
+    pub direction: f32,
+    /// Width of the velocity cone in degrees, 360 for all around.
+    pub spread: f32,
+    /// Side of the square particle in pixels.
+    pub size: f32,
+    /// Downward acceleration in pixels per second squared.
+    #[serde(default)]
+    pub gravity: f32,
+    /// RGBA colors spread evenly over the life of a particle.
+    pub colors: Vec<(f32, f32, f32, f32)>,
+}
+
+impl EmitterDef {
+    fn color_at(&self, life: f32) -> Color {
+        let rgba = |(r, g, b, a): (f32, f32, f32, f32)| Vec4::new(r, g, b, a);
+        let color = match self.colors.len() {
+            0 => Vec4::ONE,
+            1 => rgba(self.colors[0]),
+            len => {
+                let at = life.clamp(0.0, 1.0) * (len - 1) as f32;
+                let i = (at as usize).min(len - 2);
+                rgba(self.colors[i]).lerp(rgba(self.colors[i + 1]), at - i as f32)
+            }
+        };
+        Color::rgba(color.x, color.y, color.z, color.w)
+    }
+
+    /// Puts reversed ranges back in order, picking from one panics.
+    fn sorted(mut self, name: &str) -> Self {
+        let sort = |range: &mut (f32, f32), field: &str| {
+            if range.0 > range.1 {
+                warn!("{}: {} of {} is reversed", DEFINITIONS_FILE, field, name);
+                *range = (range.1, range.0);
+            }
+        };
+        sort(&mut self.lifetime, "lifetime");
+        sort(&mut self.speed, "speed");
+        self.spread = self.spread.abs();
+        self
+    }
+}
+
+/// Sent by the games for a one-off burst of the named emitter.
+pub struct SpawnParticles {
+    pub emitter: &'static str,
+    pub position: Vec3,
+}
+
+/// Keeps emitting the named emitter at `rate` from the entity it is on.
+pub struct ParticleEmitter {
+    pub emitter: &'static str,
+    /// Where the particles leave from, relative to the entity.
+    pub offset: Vec3,
+    /// Fraction of a particle owed from earlier frames.
+    owed: f32,
+}
+
+impl ParticleEmitter {
+    pub fn new(emitter: &'static str, offset: Vec3) -> Self {
+        Self {
+            emitter,
+            offset,
+            owed: 0.0,
+        }
+    }
+}
+
+// Component
+struct Particle {
+    emitter: &'static str,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+}
+
+// Resource
+struct Emitters(HashMap<String, EmitterDef>);
+
+// Resource
+/// Materials of each emitter's color steps, shared by all its particles.
+struct EmitterMaterials(HashMap<String, Vec<Handle<ColorMaterial>>>);
+
+impl EmitterMaterials {
+    fn at(&self, emitter: &str, life: f32) -> Option<&Handle<ColorMaterial>> {
+        let steps = self.0.get(emitter)?;
+        let step = (life.clamp(0.0, 1.0) * (steps.len() - 1) as f32).round() as usize;
+        steps.get(step)
+    }
+}
+
+/// Simulates particles on the CPU, each one a small colored sprite.
+///
+/// Particles are cosmetic, so they draw from the thread rng and leave the
+/// games' seeded generators alone.
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let definitions = fs::read_to_string(asset_path(DEFINITIONS_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str(&content).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", DEFINITIONS_FILE, e);
+                HashMap::new()
+            })
+            .into_iter()
+            .map(|(name, def): (String, EmitterDef)| {
+                let def = def.sorted(&name);
+                (name, def)
+            })
+            .collect();
+
+        app.add_event::<SpawnParticles>()
+            .insert_resource(Emitters(definitions))
+            .add_startup_system(emitter_materials.system())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(spawn_bursts.system())
+                    .with_system(emit_continuous.system())
+                    .with_system(update_particles.system()),
+            );
+    }
+}
+
+fn emitter_materials(
+    mut commands: Commands,
+    emitters: Res<Emitters>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let steps = emitters
+        .0
+        .iter()
+        .map(|(name, def)| {
+            let count = if def.colors.len() > 1 { COLOR_STEPS } else { 1 };
+            let steps = (0..count)
+                .map(|step| {
+                    let life = step as f32 / (count - 1).max(1) as f32;
+                    materials.add(def.color_at(life).into())
+                })
+                .collect();
+            (name.clone(), steps)
+        })
+        .collect();
+    commands.insert_resource(EmitterMaterials(steps));
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    materials: &EmitterMaterials,
+    emitter: &'static str,
+    def: &EmitterDef,
+    position: Vec3,
+) {
+    let material = match materials.at(emitter, 0.0) {
+        Some(material) => material.clone(),
+        None => return,
+    };
+    let mut rng = rand::thread_rng();
+    let half_spread = def.spread.to_radians() / 2.0;
+    let angle = def.direction.to_radians() + rng.gen_range(-half_spread..=half_spread);
+    let speed = rng.gen_range(def.speed.0..=def.speed.1);
+    let lifetime = rng.gen_range(def.lifetime.0..=def.lifetime.1);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            material,
+            sprite: Sprite::new(Vec2::splat(def.size)),
+            transform: Transform::from_translation(position),
+            ..Default::default()
+        })
+        .insert(Particle {
+            emitter,
+            velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+            age: 0.0,
+            lifetime,
+        });
+}
+
+fn spawn_bursts(
+    mut commands: Commands,
+    mut events: EventReader<SpawnParticles>,
+    emitters: Res<Emitters>,
+    materials: Res<EmitterMaterials>,
+) {
+    for SpawnParticles { emitter, position } in events.iter() {
+        let def = match emitters.0.get(*emitter) {
+            Some(def) => def,
+            None => continue,
+        };
+        for _ in 0..def.burst {
+            spawn_particle(&mut commands, &materials, emitter, def, *position);
+        }
+    }
+}
+
+fn emit_continuous(
+    mut commands: Commands,
+    time: Res<Time>,
+    emitters: Res<Emitters>,
+    materials: Res<EmitterMaterials>,
+    mut query: Query<(&mut ParticleEmitter, &Transform)>,
+) {
+    for (mut emitter, transform) in query.iter_mut() {
+        let def = match emitters.0.get(emitter.emitter) {
+            Some(def) => def,
+            None => continue,
+        };
+        emitter.owed += def.rate * time.delta_seconds();
+        while emitter.owed >= 1.0 {
+            emitter.owed -= 1.0;
+            let position = transform.translation + emitter.offset;
+            spawn_particle(&mut commands, &materials, emitter.emitter, def, position);
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    emitters: Res<Emitters>,
+    materials: Res<EmitterMaterials>,
+    mut query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Handle<ColorMaterial>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut material) in query.iter_mut() {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let def = match emitters.0.get(particle.emitter) {
+            Some(def) => def,
+            None => continue,
+        };
+
+        particle.velocity.y -= def.gravity * dt;
+        transform.translation += particle.velocity.extend(0.0) * dt;
+        if let Some(step) = materials.at(particle.emitter, particle.age / particle.lifetime) {
+            if *material != *step {
+                *material = step.clone();
+            }
+        }
+    }
+}
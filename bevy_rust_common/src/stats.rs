@@ -0,0 +1,198 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, OpenOptions},
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Fields that identify a game rather than measure it, left out of the aggregates.
+const IDENTIFIER_FIELDS: [&str; 2] = ["finished_at", "seed"];
+
+/// Append-only log with one JSON summary per finished game.
+pub struct StatsLog {
+    path: String,
+}
+
+impl StatsLog {
+    pub fn new(game: &str) -> Self {
+        Self {
+            path: format!("{}_stats.jsonl", game),
+        }
+    }
+
+    pub fn append<T: Serialize>(&self, summary: &T) -> Result<(), String> {
+        let line = serde_json::to_string(summary).map_err(|e| e.to_string())?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    }
+
+    /// Every logged game, flattened so that nested maps such as kills by
+    /// enemy type become `kills.<type>` columns.
+    fn records(&self) -> Result<Vec<BTreeMap<String, Value>>, String> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("{}: {}", self.path, e)),
+        };
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+                let mut record = BTreeMap::new();
+                flatten("", value, &mut record);
+                Ok(record)
+            })
+            .collect()
+    }
+}
+
+fn flatten(prefix: &str, value: Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, out);
+            }
+        }
+        value => {
+            out.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Entry point of the `stats` subcommand.
+///
+/// Without arguments it prints aggregates over all logged games,
+/// `--csv [FILE]` and `--json [FILE]` export every game instead, to stdout
+/// unless a file is given.
+pub fn run_command(game: &str, args: &[String]) -> Result<(), String> {
+    let records = StatsLog::new(game).records()?;
+
+    let (output, target) = match args.first().map(String::as_str) {
+        None => {
+            print!("{}", aggregates(game, &records));
+            return Ok(());
+        }
+        Some("--csv") => (to_csv(&records), args.get(1)),
+        Some("--json") => (
+            serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?,
+            args.get(1),
+        ),
+        Some(other) => return Err(format!("unknown stats option: {}", other)),
+    };
+
+    match target {
+        Some(path) => fs::write(path, output).map_err(|e| e.to_string()),
+        None => {
+            println!("{}", output);
+            Ok(())
+        }
+    }
+}
+
+fn aggregates(game: &str, records: &[BTreeMap<String, Value>]) -> String {
+    let mut out = format!("{}: {} games\n", game, records.len());
+    if records.is_empty() {
+        return out;
+    }
+
+    let mut numbers: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    let mut labels: BTreeMap<&str, BTreeMap<String, u32>> = BTreeMap::new();
+    for record in records {
+        for (key, value) in record {
+            if IDENTIFIER_FIELDS.contains(&key.as_str()) {
+                continue;
+            }
+            match value {
+                Value::Number(n) => numbers
+                    .entry(key)
+                    .or_default()
+                    .push(n.as_f64().unwrap_or(0.0)),
+                Value::String(s) => {
+                    *labels.entry(key).or_default().entry(s.clone()).or_default() += 1
+                }
+                Value::Bool(b) => {
+                    *labels
+                        .entry(key)
+                        .or_default()
+                        .entry(b.to_string())
+                        .or_default() += 1
+                }
+                _ => {}
+            }
+        }
+    }
+
+    out += &format!(
+        "\n{:<28}{:>12}{:>12}{:>12}{:>14}\n",
+        "stat", "mean", "min", "max", "total"
+    );
+    for (key, values) in numbers {
+        let total: f64 = values.iter().sum();
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        out += &format!(
+            "{:<28}{:>12.2}{:>12.2}{:>12.2}{:>14.2}\n",
+            key,
+            total / values.len() as f64,
+            min,
+            max,
+            total
+        );
+    }
+
+    for (key, counts) in labels {
+        let counts: Vec<String> = counts
+            .iter()
+            .map(|(label, count)| format!("{} {}", label, count))
+            .collect();
+        out += &format!("\n{}: {}\n", key, counts.join(", "));
+    }
+    out
+}
+
+fn to_csv(records: &[BTreeMap<String, Value>]) -> String {
+    let columns: BTreeSet<&String> = records.iter().flat_map(|r| r.keys()).collect();
+
+    let mut out = columns
+        .iter()
+        .map(|c| c.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    for record in records {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|column| match record.get(*column) {
+                Some(Value::String(s)) if s.contains([',', '"']) => {
+                    format!("\"{}\"", s.replace('"', "\"\""))
+                }
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Null) | None => String::new(),
+                Some(value) => value.to_string(),
+            })
+            .collect();
+        out += "\n";
+        out += &row.join(",");
+    }
+    out
+}
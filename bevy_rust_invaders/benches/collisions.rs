@@ -0,0 +1,74 @@
+//! Frame cost of the hit checks with thousands of lasers on screen, laser by
+//! laser against every enemy and through the spatial grid.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rust_invaders::grid::{Aabb, SpatialGrid};
+
+const WIDTH: f32 = 600.0;
+const HEIGHT: f32 = 600.0;
+const CELL_SIZE: f32 = 64.0;
+const LASER_SIZE: (f32, f32) = (4.0, 27.0);
+const ENEMY_SIZE: (f32, f32) = (42.0, 32.0);
+
+fn scatter(rng: &mut StdRng, count: usize, size: (f32, f32)) -> Vec<Aabb> {
+    (0..count)
+        .map(|_| {
+            let center = (
+                rng.gen_range(-WIDTH / 2.0..WIDTH / 2.0),
+                rng.gen_range(-HEIGHT / 2.0..HEIGHT / 2.0),
+            );
+            Aabb::new(center, size)
+        })
+        .collect()
+}
+
+/// Hits of a frame checking every laser against every enemy.
+fn brute_force(lasers: &[Aabb], enemies: &[Aabb]) -> usize {
+    lasers
+        .iter()
+        .map(|laser| enemies.iter().filter(|enemy| laser.overlaps(enemy)).count())
+        .sum()
+}
+
+/// Hits of a frame filing the enemies in the grid, then looking up the lasers.
+fn grid(grid: &mut SpatialGrid<usize>, lasers: &[Aabb], enemies: &[Aabb]) -> usize {
+    grid.clear();
+    for (i, enemy) in enemies.iter().enumerate() {
+        grid.insert(i, *enemy);
+    }
+    lasers.iter().map(|laser| grid.query(*laser).count()).sum()
+}
+
+fn collisions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("laser_hits");
+    // a wave's worth of enemies, then a screen full of them
+    for &(lasers, enemies) in &[(500, 60), (2000, 60), (8000, 60), (2000, 240), (8000, 240)] {
+        let mut rng = StdRng::seed_from_u64(lasers as u64);
+        let laser_boxes = scatter(&mut rng, lasers, LASER_SIZE);
+        let enemy_boxes = scatter(&mut rng, enemies, ENEMY_SIZE);
+        let id = format!("{}x{}", lasers, enemies);
+        let mut spatial = SpatialGrid::new(CELL_SIZE, WIDTH, HEIGHT);
+        assert_eq!(
+            brute_force(&laser_boxes, &enemy_boxes),
+            grid(&mut spatial, &laser_boxes, &enemy_boxes)
+        );
+
+        group.bench_with_input(BenchmarkId::new("brute_force", &id), &lasers, |b, _| {
+            b.iter(|| brute_force(black_box(&laser_boxes), black_box(&enemy_boxes)))
+        });
+        group.bench_with_input(BenchmarkId::new("grid", &id), &lasers, |b, _| {
+            b.iter(|| {
+                grid(
+                    &mut spatial,
+                    black_box(&laser_boxes),
+                    black_box(&enemy_boxes),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, collisions);
+criterion_main!(benches);
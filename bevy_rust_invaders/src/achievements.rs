@@ -0,0 +1,36 @@
+use bevy::prelude::*;
+use rust_common::achievements::AchievementEvent;
+
+use crate::{EnemyKilled, PlayerKilled};
+
+#[derive(Default)]
+pub struct KillCount {
+    total: u32,
+    this_life: u32,
+}
+
+/// Translates kills and deaths into achievement progress.
+pub fn report_achievements(
+    mut kills: Local<KillCount>,
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut player_killed: EventReader<PlayerKilled>,
+    mut achievements: EventWriter<AchievementEvent>,
+) {
+    for _ in enemy_killed.iter() {
+        kills.total += 1;
+        kills.this_life += 1;
+        achievements.send(AchievementEvent::Progress {
+            stat: "kills",
+            value: kills.total,
+        });
+        achievements.send(AchievementEvent::Progress {
+            stat: "kills_without_dying",
+            value: kills.this_life,
+        });
+    }
+
+    if player_killed.iter().next().is_some() {
+        kills.this_life = 0;
+        achievements.send(AchievementEvent::Trigger("player_killed"));
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+
+use bevy::prelude::*;
+use rust_common::{asset_path, menu::not_paused};
+use serde::Deserialize;
+
+use crate::{waves::check_positive, Enemy, BASIC_ENEMY, ENEMY_SPRITE};
+
+const ENEMIES_FILE: &str = "enemies.ron";
+const FLASH_SECONDS: f32 = 0.08;
+/// Tint of a damaged enemy, bright enough to wash its sprite out to white.
+const FLASH_TINT: f32 = 5.0;
+
+// region: Definitions
+/// Angles are in degrees, speeds in pixels per second.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum FirePattern {
+    Never,
+    /// One laser straight down.
+    Straight,
+    /// One laser at where the player is.
+    Aimed,
+    /// `count` lasers fanned out over `angle` around straight down.
+    Spread {
+        count: u32,
+        angle: f32,
+    },
+    /// `count` lasers all around.
+    Radial {
+        count: u32,
+    },
+    /// A missile turning after the player by up to `turn_rate` per second,
+    /// until it runs out after `lifetime` seconds and flies on straight.
+    Homing {
+        speed: f32,
+        turn_rate: f32,
+        lifetime: f32,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum Movement {
+    /// Flies the formation of its group.
+    Formation,
+    /// Flies its formation for `after` seconds, then dives at the player
+    /// with `speed` pixels per second and rejoins from the top.
+    Dive { after: f32, speed: f32 },
+}
+
+/// An enemy kind, loaded from `assets/enemies.ron`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnemyDef {
+    /// Hits it takes to destroy.
+    pub hp: u32,
+    /// Multiplies the enemy speed of the wave.
+    pub speed: f32,
+    pub points: u32,
+    pub sprite: String,
+    #[serde(default = "white")]
+    pub tint: (f32, f32, f32),
+    /// Multiplies the size of the sprite.
+    #[serde(default = "one")]
+    pub scale: f32,
+    pub fire: FirePattern,
+    /// Multiplies the seconds between two volleys of the wave.
+    #[serde(default = "one")]
+    pub cooldown: f32,
+    pub movement: Movement,
+    /// Chance to drop a power-up when destroyed, from 0 to 1.
+    #[serde(default = "drop_chance")]
+    pub drop_chance: f32,
+}
+
+fn white() -> (f32, f32, f32) {
+    (1.0, 1.0, 1.0)
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn drop_chance() -> f32 {
+    0.1
+}
+
+impl FirePattern {
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            FirePattern::Homing { lifetime, .. } => check_positive("lifetime", lifetime),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl EnemyDef {
+    /// The only enemy the game had before kinds were data.
+    fn basic() -> Self {
+        Self {
+            hp: 1,
+            speed: 1.0,
+            points: 100,
+            sprite: ENEMY_SPRITE.to_string(),
+            tint: white(),
+            scale: 1.0,
+            fire: FirePattern::Straight,
+            cooldown: 1.0,
+            movement: Movement::Formation,
+            drop_chance: drop_chance(),
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.fire.check()?;
+        check_positive("cooldown", self.cooldown)?;
+        if let Movement::Dive { after, .. } = self.movement {
+            check_positive("after", after)?;
+        }
+        Ok(())
+    }
+}
+
+// Resource
+/// Enemy kinds by the name waves refer to them with. Always knows
+/// `BASIC_ENEMY`, which stands in for unknown kinds.
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct EnemyBook(BTreeMap<String, EnemyDef>);
+
+impl EnemyBook {
+    fn load() -> Self {
+        let mut book = fs::read_to_string(asset_path(ENEMIES_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str::<Self>(&content).map_err(|e| e.to_string()))
+            .and_then(|book| {
+                for (kind, def) in book.0.iter() {
+                    def.check().map_err(|e| format!("{}: {}", kind, e))?;
+                }
+                Ok(book)
+            })
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", ENEMIES_FILE, e);
+                Self(BTreeMap::new())
+            });
+        book.0
+            .entry(BASIC_ENEMY.to_string())
+            .or_insert_with(EnemyDef::basic);
+        book
+    }
+
+    pub fn contains(&self, kind: &str) -> bool {
+        self.0.contains_key(kind)
+    }
+
+    pub fn get(&self, kind: &str) -> &EnemyDef {
+        self.0.get(kind).unwrap_or_else(|| &self.0[BASIC_ENEMY])
+    }
+}
+// endregion: Definitions
+
+// region: Resources
+struct Looks {
+    normal: Handle<ColorMaterial>,
+    flash: Handle<ColorMaterial>,
+}
+
+/// Materials of every enemy kind, shared by all enemies of that kind.
+pub struct EnemyLooks(HashMap<String, Looks>);
+
+impl EnemyLooks {
+    pub fn material(&self, kind: &str) -> Handle<ColorMaterial> {
+        self.looks(kind).normal.clone()
+    }
+
+    fn looks(&self, kind: &str) -> &Looks {
+        self.0.get(kind).unwrap_or_else(|| &self.0[BASIC_ENEMY])
+    }
+}
+// endregion: Resources
+
+// region: Components
+/// The enemy was just damaged and shows its flash material.
+pub struct HitFlash(Timer);
+
+impl Default for HitFlash {
+    fn default() -> Self {
+        Self(Timer::from_seconds(FLASH_SECONDS, false))
+    }
+}
+// endregion: Components
+
+/// Enemy kinds of `assets/enemies.ron`: how tough, fast and valuable they
+/// are, how they look, fire and move.
+pub struct ArchetypePlugin;
+
+impl Plugin for ArchetypePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(EnemyBook::load())
+            .add_startup_system(enemy_looks.system())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(hit_flash.system()),
+            );
+    }
+}
+
+fn enemy_looks(
+    mut commands: Commands,
+    book: Res<EnemyBook>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let looks = book
+        .0
+        .iter()
+        .map(|(kind, def)| {
+            let texture = asset_server.load(def.sprite.as_str());
+            let (r, g, b) = def.tint;
+            let looks = Looks {
+                normal: materials.add(ColorMaterial::modulated_texture(
+                    texture.clone(),
+                    Color::rgb(r, g, b),
+                )),
+                flash: materials.add(ColorMaterial::modulated_texture(
+                    texture,
+                    Color::rgb(r * FLASH_TINT, g * FLASH_TINT, b * FLASH_TINT),
+                )),
+            };
+            (kind.clone(), looks)
+        })
+        .collect();
+    commands.insert_resource(EnemyLooks(looks));
+}
+
+fn hit_flash(
+    mut commands: Commands,
+    time: Res<Time>,
+    looks: Res<EnemyLooks>,
+    mut query: Query<(Entity, &Enemy, &mut Handle<ColorMaterial>, &mut HitFlash)>,
+) {
+    for (entity, enemy, mut material, mut flash) in query.iter_mut() {
+        let looks = looks.looks(&enemy.kind);
+        if flash.0.tick(time.delta()).finished() {
+            *material = looks.normal.clone();
+            commands.entity(entity).remove::<HitFlash>();
+        } else if *material != looks.flash {
+            *material = looks.flash.clone();
+        }
+    }
+}
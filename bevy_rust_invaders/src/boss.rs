@@ -0,0 +1,399 @@
+use std::{collections::BTreeMap, fs};
+
+use bevy::prelude::*;
+use rand::Rng;
+use rust_common::{asset_path, menu::not_paused};
+use serde::Deserialize;
+
+use crate::{
+    archetype::{EnemyBook, EnemyLooks, FirePattern},
+    damage::{DeathEvent, DespawnEvent},
+    enemy::{fire_cooldown, fire_pattern},
+    waves::{check_positive, Wave, WaveStage},
+    ActiveEnemies, Enemy, ExplosionToSpawn, GamePhase, GameRng, Health, Invulnerable, Materials,
+    Player, TimeStep, WinSize, SCALE,
+};
+
+const BOSSES_FILE: &str = "bosses.ron";
+/// Seconds between two explosions of a dying boss.
+const EXPLOSION_SECONDS: f32 = 0.15;
+/// Explosions after the last part is gone.
+const FINAL_EXPLOSIONS: u32 = 6;
+
+// region: Definitions
+#[derive(Deserialize, Clone, Debug)]
+pub struct PartDef {
+    /// Enemy kind of `assets/enemies.ron` giving the part its looks, hit
+    /// points and score. Its fire and movement don't apply.
+    pub kind: String,
+    /// Position relative to the center of the boss.
+    pub offset: (f32, f32),
+    /// Destroying the core destroys the whole boss.
+    #[serde(default)]
+    pub core: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PhaseDef {
+    /// The phase starts once the boss has less than this fraction of its
+    /// hit points left.
+    pub below: f32,
+    /// Fired by every part left.
+    pub fire: FirePattern,
+    /// Average seconds between two volleys of a part.
+    pub cooldown: f32,
+    /// Pixels per second the boss sways side to side.
+    pub sway: f32,
+}
+
+/// A boss, loaded from `assets/bosses.ron`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct BossDef {
+    pub parts: Vec<PartDef>,
+    /// Height above the window center the boss enters to.
+    pub hover: f32,
+    /// Pixels per second of the entrance.
+    pub entrance_speed: f32,
+    /// In order of `below`, from the highest.
+    pub phases: Vec<PhaseDef>,
+}
+
+// Resource
+/// Bosses by the name waves refer to them with.
+#[derive(Deserialize, Default)]
+#[serde(transparent)]
+pub struct BossBook(BTreeMap<String, BossDef>);
+
+impl BossBook {
+    fn load() -> Self {
+        fs::read_to_string(asset_path(BOSSES_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str::<Self>(&content).map_err(|e| e.to_string()))
+            .and_then(|book| {
+                for (name, def) in book.0.iter() {
+                    for phase in def.phases.iter() {
+                        phase
+                            .fire
+                            .check()
+                            .and_then(|_| check_positive("cooldown", phase.cooldown))
+                            .map_err(|e| format!("{}: {}", name, e))?;
+                    }
+                }
+                Ok(book)
+            })
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", BOSSES_FILE, e);
+                Self::default()
+            })
+    }
+}
+// endregion: Definitions
+
+// region: Components
+enum BossStage {
+    /// Flying in, parts can't be hit yet.
+    Entering,
+    Fighting,
+    /// The core is destroyed; the parts left blow up one by one.
+    Dying {
+        timer: Timer,
+        explosions: u32,
+    },
+}
+
+pub struct Boss {
+    pub name: String,
+    def: BossDef,
+    position: Vec2,
+    /// Sway direction, 1.0 to the right.
+    direction: f32,
+    phase: usize,
+    stage: BossStage,
+    parts: Vec<Entity>,
+    core: Entity,
+    max_hp: u32,
+    /// Fraction of the hit points left, for the HUD.
+    pub health: f32,
+}
+
+/// Part of a boss, placed at `offset` from its center.
+struct BossPart {
+    offset: Vec2,
+}
+/// Time until a part fires again.
+struct PartFire(Timer);
+// endregion: Components
+
+/// Bosses named by waves: multi-part enemies entering from the top, changing
+/// attacks as they lose hit points and going down in a chain of explosions.
+/// Their parts are enemies, hit and scored like any other.
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(BossBook::load()).add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(boss_spawn.system())
+                .with_system(boss_movement.system().label("movement"))
+                .with_system(boss_phases.system())
+                .with_system(boss_fire.system())
+                .with_system(boss_death.system()),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn boss_spawn(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    bosses: Res<BossBook>,
+    enemies: Res<EnemyBook>,
+    looks: Res<EnemyLooks>,
+    win_size: Res<WinSize>,
+    mut rng: ResMut<GameRng>,
+) {
+    let name = match &mut wave.stage {
+        WaveStage::Fighting { def, .. } => match def.boss.take() {
+            Some(name) => name,
+            None => return,
+        },
+        _ => return,
+    };
+    let def = match bosses.0.get(&name) {
+        Some(def) => def.clone(),
+        None => {
+            warn!("wave {} meets unknown boss {}", wave.number, name);
+            return;
+        }
+    };
+    info!("boss {} enters", name);
+
+    let position = Vec2::new(0.0, win_size.height / 2.0 + 150.0);
+    let mut parts = Vec::new();
+    let mut core = None;
+    let mut max_hp = 0;
+    for part in def.parts.iter() {
+        let enemy = enemies.get(&part.kind);
+        let offset = Vec2::new(part.offset.0, part.offset.1);
+        let scale = SCALE * enemy.scale;
+        let entity = commands
+            .spawn_bundle(SpriteBundle {
+                material: looks.material(&part.kind),
+                transform: Transform {
+                    translation: (position + offset).extend(10.0),
+                    scale: Vec3::new(scale, scale, 1.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Enemy {
+                kind: part.kind.clone(),
+            })
+            .insert(Health(enemy.hp))
+            .insert(BossPart { offset })
+            // lasers pass through until the entrance is over
+            .insert(Invulnerable(Timer::default()))
+            .id();
+        active_enemies.0 += 1;
+        max_hp += enemy.hp;
+        parts.push(entity);
+        if part.core {
+            core = Some(entity);
+        }
+    }
+    // without a core, the boss goes down with its last part
+    let core = match core.or_else(|| parts.last().copied()) {
+        Some(core) => core,
+        None => return,
+    };
+
+    commands.spawn().insert(Boss {
+        name,
+        def,
+        position,
+        direction: if rng.rng.gen::<bool>() { 1.0 } else { -1.0 },
+        phase: 0,
+        stage: BossStage::Entering,
+        parts,
+        core,
+        max_hp,
+        health: 1.0,
+    });
+}
+
+fn boss_movement(
+    mut commands: Commands,
+    step: Res<TimeStep>,
+    win_size: Res<WinSize>,
+    mut boss_query: Query<&mut Boss>,
+    mut part_query: Query<(&BossPart, &mut Transform)>,
+) {
+    for mut boss in boss_query.iter_mut() {
+        let boss = &mut *boss;
+        match boss.stage {
+            BossStage::Entering => {
+                boss.position.y -= boss.def.entrance_speed * step.0;
+                if boss.position.y <= boss.def.hover {
+                    boss.position.y = boss.def.hover;
+                    boss.stage = BossStage::Fighting;
+                    for &part in boss.parts.iter() {
+                        commands.entity(part).remove::<Invulnerable>();
+                    }
+                }
+            }
+            BossStage::Fighting => {
+                let sway = boss.def.phases.get(boss.phase).map_or(0.0, |p| p.sway);
+                let half_width = boss
+                    .def
+                    .parts
+                    .iter()
+                    .map(|part| part.offset.0.abs())
+                    .fold(0.0, f32::max)
+                    + 60.0;
+                let limit = (win_size.width / 2.0 - half_width).max(0.0);
+                boss.position.x += boss.direction * sway * step.0;
+                if boss.position.x.abs() > limit {
+                    boss.position.x = boss.position.x.clamp(-limit, limit);
+                    boss.direction = -boss.direction;
+                }
+            }
+            BossStage::Dying { .. } => {}
+        }
+
+        for &part in boss.parts.iter() {
+            if let Ok((part, mut tf)) = part_query.get_mut(part) {
+                tf.translation.x = boss.position.x + part.offset.x;
+                tf.translation.y = boss.position.y + part.offset.y;
+            }
+        }
+    }
+}
+
+fn boss_phases(mut boss_query: Query<&mut Boss>, health_query: Query<&Health>) {
+    for mut boss in boss_query.iter_mut() {
+        let hp: u32 = boss
+            .parts
+            .iter()
+            .filter_map(|&part| health_query.get(part).ok())
+            .map(|health| health.0)
+            .sum();
+        boss.health = hp as f32 / boss.max_hp.max(1) as f32;
+
+        let health = boss.health;
+        while boss
+            .def
+            .phases
+            .get(boss.phase + 1)
+            .map_or(false, |next| health < next.below)
+        {
+            boss.phase += 1;
+            info!("boss {} enters phase {}", boss.name, boss.phase + 1);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn boss_fire(
+    mut commands: Commands,
+    time: Res<Time>,
+    materials: Res<Materials>,
+    phase: Res<GamePhase>,
+    mut rng: ResMut<GameRng>,
+    boss_query: Query<&Boss>,
+    player_query: Query<&Transform, With<Player>>,
+    mut part_query: Query<(Entity, &Transform, Option<&mut PartFire>), With<BossPart>>,
+) {
+    if !phase.playing() {
+        return;
+    }
+    let player = player_query
+        .single()
+        .ok()
+        .map(|tf| Vec2::from(tf.translation));
+
+    for boss in boss_query.iter() {
+        let boss_phase = match (&boss.stage, boss.def.phases.get(boss.phase)) {
+            (BossStage::Fighting, Some(boss_phase)) => boss_phase,
+            _ => continue,
+        };
+        for &part in boss.parts.iter() {
+            let (entity, tf, timer) = match part_query.get_mut(part) {
+                Ok(part) => part,
+                Err(_) => continue,
+            };
+            let cooldown = fire_cooldown(boss_phase.cooldown, &mut rng.rng);
+            match timer {
+                Some(mut timer) => {
+                    if !timer.0.tick(time.delta()).finished() {
+                        continue;
+                    }
+                    timer.0 = Timer::from_seconds(cooldown, false);
+                }
+                None => {
+                    // parts start at different times
+                    commands
+                        .entity(entity)
+                        .insert(PartFire(Timer::from_seconds(cooldown, false)));
+                    continue;
+                }
+            }
+
+            let origin = Vec2::new(tf.translation.x, tf.translation.y - 15.0);
+            fire_pattern(&mut commands, &materials, boss_phase.fire, origin, player);
+        }
+    }
+}
+
+fn boss_death(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut deaths: EventWriter<DeathEvent>,
+    mut despawns: EventWriter<DespawnEvent>,
+    mut rng: ResMut<GameRng>,
+    mut boss_query: Query<(Entity, &mut Boss)>,
+    part_query: Query<&Transform, With<BossPart>>,
+) {
+    for (boss_entity, mut boss) in boss_query.iter_mut() {
+        let boss = &mut *boss;
+        // parts destroyed by lasers are gone already
+        boss.parts.retain(|&part| part_query.get(part).is_ok());
+
+        if !matches!(boss.stage, BossStage::Dying { .. }) {
+            if boss.parts.contains(&boss.core) {
+                continue;
+            }
+            info!("boss {} destroyed", boss.name);
+            boss.stage = BossStage::Dying {
+                timer: Timer::from_seconds(EXPLOSION_SECONDS, true),
+                explosions: FINAL_EXPLOSIONS,
+            };
+        }
+
+        if let BossStage::Dying { timer, explosions } = &mut boss.stage {
+            if !timer.tick(time.delta()).just_finished() {
+                continue;
+            }
+            // one part after the other, then a few more all over the boss
+            if let Some(part) = boss.parts.pop() {
+                // no score for the parts the player didn't destroy
+                deaths.send(DeathEvent {
+                    entity: part,
+                    scored: false,
+                });
+            } else if *explosions > 0 {
+                *explosions -= 1;
+                let spread = Vec2::new(
+                    rng.rng.gen_range(-80.0..80.0),
+                    rng.rng.gen_range(-30.0..30.0),
+                );
+                commands
+                    .spawn()
+                    .insert(ExplosionToSpawn((boss.position + spread).extend(0.0)));
+            } else {
+                despawns.send(DespawnEvent(boss_entity));
+            }
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use bevy::{
+    prelude::*,
+    render::texture::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+};
+use rand::Rng;
+use rust_common::menu::not_paused;
+use rust_invaders::grid::Aabb;
+
+use crate::{
+    damage::{BunkerHit, DespawnEvent, HitEvent},
+    sprite_aabb,
+    waves::{Wave, WaveStage},
+    CollisionGrid, GameRng, Laser, Velocity, WinSize,
+};
+
+/// Pixels of the mask across and down.
+const MASK_WIDTH: usize = 24;
+const MASK_HEIGHT: usize = 16;
+/// Screen pixels per mask pixel.
+const PIXEL: f32 = 2.0;
+/// Height of the bunkers above the bottom of the window.
+const BUNKER_HEIGHT: f32 = 110.0;
+/// Mask pixels around the impact a laser blasts away, and the ragged edge
+/// beyond where each one goes with even odds.
+const BLAST_RADIUS: f32 = 2.5;
+const RAGGED_EDGE: f32 = 1.5;
+const COLOR: [u8; 4] = [90, 200, 90, 255];
+
+// region: Components
+/// A shield lasers of both sides chip away at, with a pixel mask of what is
+/// left of it shown as its texture.
+pub struct Bunker {
+    /// Row by row from the top.
+    solid: Vec<bool>,
+    texture: Handle<Texture>,
+}
+
+impl Bunker {
+    /// The classic arch: cut top corners and room below for the ship.
+    fn shape() -> Vec<bool> {
+        let mut solid = vec![true; MASK_WIDTH * MASK_HEIGHT];
+        for y in 0..MASK_HEIGHT {
+            for x in 0..MASK_WIDTH {
+                let from_side = x.min(MASK_WIDTH - 1 - x);
+                let corner = y < 4 && from_side + y < 4;
+                let arch = y >= MASK_HEIGHT - 5 && (8..MASK_WIDTH - 8).contains(&x);
+                if corner || arch {
+                    solid[y * MASK_WIDTH + x] = false;
+                }
+            }
+        }
+        solid
+    }
+
+    fn size() -> Vec2 {
+        Vec2::new(MASK_WIDTH as f32, MASK_HEIGHT as f32) * PIXEL
+    }
+
+    /// Mask pixel a laser flying up or down through `aabb` hits first, for
+    /// a bunker at `center`.
+    fn impact(&self, center: Vec2, aabb: Aabb, upwards: bool) -> Option<(usize, usize)> {
+        let top_left = center + Vec2::new(-Self::size().x, Self::size().y) / 2.0;
+        let column = |x: f32| ((x - top_left.x) / PIXEL).floor();
+        let row = |y: f32| ((top_left.y - y) / PIXEL).floor();
+        let half = (aabb.size.0 / 2.0, aabb.size.1 / 2.0);
+
+        let x0 = column(aabb.center.0 - half.0).max(0.0) as usize;
+        let x1 = column(aabb.center.0 + half.0).min(MASK_WIDTH as f32 - 1.0);
+        let y0 = row(aabb.center.1 + half.1).max(0.0) as usize;
+        let y1 = row(aabb.center.1 - half.1).min(MASK_HEIGHT as f32 - 1.0);
+        if x1 < 0.0 || y1 < 0.0 {
+            return None;
+        }
+        let (x1, y1) = (x1 as usize, y1 as usize);
+
+        let hit = |y: usize| {
+            (x0..=x1)
+                .find(|&x| self.solid[y * MASK_WIDTH + x])
+                .map(|x| (x, y))
+        };
+        // lasers flying up meet the bottom rows first
+        if upwards {
+            (y0..=y1).rev().find_map(hit)
+        } else {
+            (y0..=y1).find_map(hit)
+        }
+    }
+
+    /// Blasts a ragged hole around mask pixel `at`.
+    fn erode(&mut self, at: (usize, usize), rng: &mut impl Rng) {
+        for y in 0..MASK_HEIGHT {
+            for x in 0..MASK_WIDTH {
+                let distance = Vec2::new(x as f32 - at.0 as f32, y as f32 - at.1 as f32).length();
+                if distance <= BLAST_RADIUS
+                    || (distance <= BLAST_RADIUS + RAGGED_EDGE && rng.gen::<bool>())
+                {
+                    self.solid[y * MASK_WIDTH + x] = false;
+                }
+            }
+        }
+    }
+
+    fn destroyed(&self) -> bool {
+        !self.solid.contains(&true)
+    }
+
+    /// Writes the mask into the texture, transparent where it is gone.
+    fn paint(&self, texture: &mut Texture) {
+        for (pixel, &solid) in texture.data.chunks_exact_mut(4).zip(self.solid.iter()) {
+            pixel[3] = if solid { COLOR[3] } else { 0 };
+        }
+    }
+}
+// endregion: Components
+
+/// Bunkers between the player and the enemies, as many as the wave defines.
+/// They are put up anew at the start of every wave.
+pub struct BunkerPlugin;
+
+impl Plugin for BunkerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(respawn_bunkers.system())
+                .with_system(
+                    laser_hit_bunker
+                        .system()
+                        .label("collision")
+                        .after("collision_grid"),
+                )
+                .with_system(erode_bunkers.system().after("apply_hits")),
+        );
+    }
+}
+
+fn respawn_bunkers(
+    mut commands: Commands,
+    mut spawned: Local<bool>,
+    wave: Res<Wave>,
+    win_size: Res<WinSize>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    bunker_query: Query<Entity, With<Bunker>>,
+) {
+    let count = match &wave.stage {
+        WaveStage::Fighting { def, .. } if !*spawned => def.bunkers,
+        WaveStage::Fighting { .. } => return,
+        WaveStage::Breather(_) => {
+            *spawned = false;
+            return;
+        }
+    };
+    *spawned = true;
+
+    for entity in bunker_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let y = -win_size.height / 2.0 + BUNKER_HEIGHT;
+    for i in 0..count {
+        // evenly spread, as far from each other as from the window sides
+        let x = win_size.width * ((i + 1) as f32 / (count + 1) as f32 - 0.5);
+
+        let solid = Bunker::shape();
+        let mut texture = Texture::new_fill(
+            Extent3d::new(MASK_WIDTH as u32, MASK_HEIGHT as u32, 1),
+            TextureDimension::D2,
+            &COLOR,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        // crisp pixels when scaled up
+        texture.sampler.mag_filter = FilterMode::Nearest;
+        texture.sampler.min_filter = FilterMode::Nearest;
+        let bunker = Bunker {
+            solid,
+            texture: textures.add(texture),
+        };
+        if let Some(texture) = textures.get_mut(&bunker.texture) {
+            bunker.paint(texture);
+        }
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(bunker.texture.clone().into()),
+                sprite: Sprite::new(Bunker::size()),
+                transform: Transform::from_xyz(x, y, 5.0),
+                ..Default::default()
+            })
+            .insert(bunker);
+    }
+}
+
+fn laser_hit_bunker(
+    grid: Res<CollisionGrid>,
+    laser_query: Query<(Entity, &Transform, &Sprite, &Velocity), With<Laser>>,
+    bunker_query: Query<(&Transform, &Bunker)>,
+    mut hits: EventWriter<HitEvent>,
+) {
+    if grid.bunkers.is_empty() {
+        return;
+    }
+    for (laser_entity, laser_tf, laser_sprite, velocity) in laser_query.iter() {
+        let laser_box = sprite_aabb(laser_tf, laser_sprite);
+        for (bunker_entity, _) in grid.bunkers.query(laser_box) {
+            let (bunker_tf, bunker) = match bunker_query.get(bunker_entity) {
+                Ok(bunker) => bunker,
+                Err(_) => continue,
+            };
+            let center = Vec2::from(bunker_tf.translation);
+            if bunker
+                .impact(center, laser_box, velocity.0.y > 0.0)
+                .is_some()
+            {
+                hits.send(HitEvent {
+                    attacker: laser_entity,
+                    target: bunker_entity,
+                    damage: 1,
+                });
+                break;
+            }
+        }
+    }
+}
+
+/// Blasts a hole where a laser hit, while the laser is still around.
+fn erode_bunkers(
+    mut hits: EventReader<BunkerHit>,
+    mut rng: ResMut<GameRng>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut despawns: EventWriter<DespawnEvent>,
+    laser_query: Query<(&Transform, &Sprite, &Velocity), With<Laser>>,
+    mut bunker_query: Query<(Entity, &Transform, &mut Bunker)>,
+) {
+    for hit in hits.iter() {
+        let (bunker_entity, bunker_tf, mut bunker) = match bunker_query.get_mut(hit.bunker) {
+            Ok(bunker) => bunker,
+            Err(_) => continue,
+        };
+        let (laser_tf, laser_sprite, velocity) = match laser_query.get(hit.laser) {
+            Ok(laser) => laser,
+            Err(_) => continue,
+        };
+        let center = Vec2::from(bunker_tf.translation);
+        let laser_box = sprite_aabb(laser_tf, laser_sprite);
+        // an earlier laser of this frame may have blasted the spot already
+        let at = match bunker.impact(center, laser_box, velocity.0.y > 0.0) {
+            Some(at) => at,
+            None => continue,
+        };
+
+        bunker.erode(at, &mut rng.rng);
+        if bunker.destroyed() {
+            despawns.send(DespawnEvent(bunker_entity));
+        } else if let Some(texture) = textures.get_mut(&bunker.texture) {
+            bunker.paint(texture);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn bunker() -> Bunker {
+        Bunker {
+            solid: Bunker::shape(),
+            texture: Handle::default(),
+        }
+    }
+
+    /// A laser half a mask pixel wide, as tall as the bunker, over the middle
+    /// of mask column `x`.
+    fn laser(x: usize) -> Aabb {
+        let left = -Bunker::size().x / 2.0 + x as f32 * PIXEL;
+        Aabb::new((left + PIXEL / 2.0, 0.0), (PIXEL / 2.0, Bunker::size().y))
+    }
+
+    #[test]
+    fn lasers_hit_the_side_they_come_from() {
+        let bunker = bunker();
+        // the arch is open at the bottom
+        assert_eq!(bunker.impact(Vec2::ZERO, laser(12), true), Some((12, 10)));
+        assert_eq!(bunker.impact(Vec2::ZERO, laser(12), false), Some((12, 0)));
+        // and the top corners are cut
+        assert_eq!(bunker.impact(Vec2::ZERO, laser(0), false), Some((0, 4)));
+        assert_eq!(bunker.impact(Vec2::ZERO, laser(0), true), Some((0, 15)));
+        // relative to where the bunker is
+        let center = Vec2::new(100.0, -50.0);
+        let mut moved = laser(12);
+        moved.center = (moved.center.0 + center.x, moved.center.1 + center.y);
+        assert_eq!(bunker.impact(center, moved, true), Some((12, 10)));
+    }
+
+    #[test]
+    fn lasers_beside_the_bunker_miss() {
+        let bunker = bunker();
+        let beside = Aabb::new((Bunker::size().x, 0.0), (PIXEL, Bunker::size().y));
+        assert_eq!(bunker.impact(Vec2::ZERO, beside, true), None);
+        let above = Aabb::new((0.0, Bunker::size().y), (PIXEL, PIXEL));
+        assert_eq!(bunker.impact(Vec2::ZERO, above, false), None);
+    }
+
+    #[test]
+    fn erosion_blasts_a_ragged_hole() {
+        let mut bunker = bunker();
+        let mut rng = StdRng::seed_from_u64(7);
+        let at = (12, 6);
+        bunker.erode(at, &mut rng);
+
+        for y in 0..MASK_HEIGHT {
+            for x in 0..MASK_WIDTH {
+                let distance = Vec2::new(x as f32 - at.0 as f32, y as f32 - at.1 as f32).length();
+                let solid = bunker.solid[y * MASK_WIDTH + x];
+                if distance <= BLAST_RADIUS {
+                    assert!(!solid, "({}, {}) is left", x, y);
+                } else if distance > BLAST_RADIUS + RAGGED_EDGE {
+                    assert_eq!(solid, Bunker::shape()[y * MASK_WIDTH + x]);
+                }
+            }
+        }
+        // the laser now flies through the hole to the next solid pixel
+        assert_eq!(bunker.impact(Vec2::ZERO, laser(12), false), Some((12, 0)));
+        assert_eq!(bunker.impact(Vec2::ZERO, laser(12), true), Some((12, 10)));
+        // and one already inside the hole stops at the ragged edge under it
+        let mut inside = laser(12);
+        let top = Bunker::size().y / 2.0 - (at.1 as f32 + 0.5) * PIXEL;
+        inside.center.1 = (top - Bunker::size().y / 2.0) / 2.0;
+        inside.size.1 = top + Bunker::size().y / 2.0;
+        assert_eq!(bunker.impact(Vec2::ZERO, inside, false), Some((12, 9)));
+        assert!(!bunker.destroyed());
+    }
+
+    #[test]
+    fn destroyed_once_nothing_is_left() {
+        let mut bunker = bunker();
+        let mut rng = StdRng::seed_from_u64(7);
+        // blasts closer together than their radius cover every pixel
+        for y in (0..MASK_HEIGHT).step_by(3) {
+            for x in (0..MASK_WIDTH).step_by(3) {
+                assert!(!bunker.destroyed());
+                bunker.erode((x, y), &mut rng);
+            }
+        }
+        assert!(bunker.destroyed());
+    }
+}
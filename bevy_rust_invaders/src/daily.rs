@@ -0,0 +1,94 @@
+use bevy::{app::AppExit, prelude::*};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rust_common::daily::{DailyChallenge, DailyLeaderboard};
+
+use crate::{Difficulty, EnemyKilled, GameRng, LifeRules, PlayerKilled, PlayerState};
+
+// region: Resources
+pub struct DailyRun {
+    challenge: DailyChallenge,
+    leaderboard: DailyLeaderboard,
+    kills: u32,
+}
+// endregion: Resources
+
+/// A daily run is played with a single life and no continues, and is scored
+/// by the number of enemies destroyed until the player is destroyed.
+pub struct DailyPlugin(pub DailyChallenge);
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let challenge = self.0.clone();
+        let mut leaderboard = challenge.leaderboard();
+        if let Err(e) = leaderboard.begin_attempt(&challenge.player) {
+            warn!("could not save daily leaderboard: {}", e);
+        }
+
+        // The level gets its own generator so the enemy waves do not depend
+        // on how many numbers the level needed.
+        let mut level_rng = StdRng::seed_from_u64(challenge.seed);
+        let difficulty = Difficulty {
+            extra_active: level_rng.gen_range(0..=2),
+            speed_scale: level_rng.gen_range(0.8..=1.2),
+        };
+        info!(
+            "daily challenge {}: {} more enemies at once, speed x{:.2}",
+            challenge.date, difficulty.extra_active, difficulty.speed_scale
+        );
+
+        let rules = LifeRules {
+            lives: 1,
+            continues: 0,
+        };
+        app.insert_resource(PlayerState::new_game(&rules))
+            .insert_resource(rules)
+            .insert_resource(difficulty)
+            .insert_resource(GameRng::from_seed(challenge.seed))
+            .insert_resource(DailyRun {
+                challenge,
+                leaderboard,
+                kills: 0,
+            })
+            .add_system(daily_score.system());
+    }
+}
+
+fn daily_score(
+    mut daily: ResMut<DailyRun>,
+    mut rng: ResMut<GameRng>,
+    mut app_exit: EventWriter<AppExit>,
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut player_killed: EventReader<PlayerKilled>,
+) {
+    // only enemies the player destroyed, not crashed divers or the ones
+    // cleared away on a restart
+    daily.kills += enemy_killed.iter().count() as u32;
+
+    // with a single life, the first hit ends the attempt
+    if player_killed.iter().count() > 0 {
+        let DailyRun {
+            challenge,
+            leaderboard,
+            kills,
+        } = &mut *daily;
+
+        match leaderboard.submit(&challenge.player, *kills) {
+            Ok(rank) => info!(
+                "daily challenge {}: destroyed {} enemies, rank {} of {}",
+                challenge.date,
+                kills,
+                rank,
+                leaderboard.entries().len()
+            ),
+            Err(e) => warn!("could not save daily leaderboard: {}", e),
+        }
+
+        if challenge.one_attempt {
+            app_exit.send(AppExit);
+        } else {
+            // Every retry replays the same enemy waves.
+            *kills = 0;
+            *rng = GameRng::from_seed(challenge.seed);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rust_common::{audio::PlaySound, menu::not_paused, particles::SpawnParticles};
+
+use crate::{
+    archetype::HitFlash,
+    bunker::Bunker,
+    enemy::Diving,
+    powerup::{self, PowerUps},
+    ActiveEnemies, Enemy, EnemyKilled, ExplosionToSpawn, FromEnemy, FromPlayer, Health,
+    Invulnerable, Laser, Pierce, Player, PlayerKilled, PlayerState,
+};
+
+// region: Events
+/// `attacker` touched `target`, sent by the collision systems and by smart
+/// bombs. Whether it hurts is up to `apply_hits`.
+pub struct HitEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub damage: u32,
+}
+
+/// An enemy ran out of hit points or the player lost a life.
+pub struct DeathEvent {
+    pub entity: Entity,
+    /// Destroyed by the player, so it counts for score and drops.
+    pub scored: bool,
+}
+
+/// Despawns the entity after the update, however often it is sent.
+pub struct DespawnEvent(pub Entity);
+
+/// `apply_hits` let `laser` hit `bunker`, which erodes where the laser is.
+pub struct BunkerHit {
+    pub laser: Entity,
+    pub bunker: Entity,
+}
+// endregion: Events
+
+/// Turns hits into damage and deaths into score, explosions and despawns,
+/// so every rule about who can hurt whom lives in one place.
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<HitEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<DespawnEvent>()
+            .add_event::<BunkerHit>()
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(apply_hits.system().label("apply_hits").after("collision"))
+                    .with_system(handle_deaths.system().after("apply_hits")),
+            )
+            // after the Update commands, so every system saw the entities
+            .add_system_to_stage(CoreStage::PostUpdate, despawn_once.system());
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Side {
+    Player,
+    Enemies,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_hits(
+    mut commands: Commands,
+    mut hits: EventReader<HitEvent>,
+    mut deaths: EventWriter<DeathEvent>,
+    mut despawns: EventWriter<DespawnEvent>,
+    mut bunker_hits: EventWriter<BunkerHit>,
+    mut particles: EventWriter<SpawnParticles>,
+    mut power_ups: ResMut<PowerUps>,
+    player_side: Query<Entity, Or<(With<Player>, With<FromPlayer>)>>,
+    enemy_side: Query<Entity, Or<(With<Enemy>, With<FromEnemy>)>>,
+    invulnerable_query: Query<Entity, With<Invulnerable>>,
+    laser_query: Query<(&Transform, Option<&Pierce>), With<Laser>>,
+    diver_query: Query<Entity, (With<Enemy>, With<Diving>)>,
+    player_query: Query<Entity, With<Player>>,
+    bunker_query: Query<Entity, With<Bunker>>,
+    mut health_query: Query<&mut Health>,
+) {
+    let side = |entity| {
+        if player_side.get(entity).is_ok() {
+            Some(Side::Player)
+        } else if enemy_side.get(entity).is_ok() {
+            Some(Side::Enemies)
+        } else {
+            None
+        }
+    };
+    // dead or spent this frame, despawned only after the update
+    let mut gone: HashSet<Entity> = HashSet::new();
+    // hit once this frame already, as good as invulnerable until the
+    // grace period is inserted
+    let mut spared: HashSet<Entity> = HashSet::new();
+
+    for hit in hits.iter() {
+        if gone.contains(&hit.attacker)
+            || gone.contains(&hit.target)
+            || spared.contains(&hit.target)
+            || invulnerable_query.get(hit.target).is_ok()
+        {
+            continue;
+        }
+        // no friendly fire, bunkers stop either side
+        let bunker = bunker_query.get(hit.target).is_ok();
+        let attacker_side = match (side(hit.attacker), side(hit.target)) {
+            (Some(attacker), Some(target)) if attacker != target => attacker,
+            (Some(attacker), None) if bunker => attacker,
+            _ => continue,
+        };
+
+        // lasers are spent unless they pierce an enemy, divers crash
+        if let Ok((laser_tf, pierce)) = laser_query.get(hit.attacker) {
+            particles.send(SpawnParticles {
+                emitter: "laser_impact",
+                position: laser_tf.translation,
+            });
+            if pierce.is_none() || bunker {
+                gone.insert(hit.attacker);
+                despawns.send(DespawnEvent(hit.attacker));
+            }
+            if bunker {
+                bunker_hits.send(BunkerHit {
+                    laser: hit.attacker,
+                    bunker: hit.target,
+                });
+            }
+        } else if diver_query.get(hit.attacker).is_ok() {
+            gone.insert(hit.attacker);
+            deaths.send(DeathEvent {
+                entity: hit.attacker,
+                scored: false,
+            });
+        }
+
+        if player_query.get(hit.target).is_ok() {
+            if power_ups.absorb_hit() {
+                spared.insert(hit.target);
+                commands.entity(hit.target).insert(powerup::shield_grace());
+            } else {
+                gone.insert(hit.target);
+                deaths.send(DeathEvent {
+                    entity: hit.target,
+                    scored: false,
+                });
+            }
+        } else if let Ok(mut health) = health_query.get_mut(hit.target) {
+            health.0 = health.0.saturating_sub(hit.damage);
+            if health.0 == 0 {
+                gone.insert(hit.target);
+                deaths.send(DeathEvent {
+                    entity: hit.target,
+                    scored: attacker_side == Side::Player,
+                });
+            } else {
+                commands.entity(hit.target).insert(HitFlash::default());
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_deaths(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut deaths: EventReader<DeathEvent>,
+    mut despawns: EventWriter<DespawnEvent>,
+    mut player_state: ResMut<PlayerState>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    mut enemy_killed: EventWriter<EnemyKilled>,
+    mut player_killed: EventWriter<PlayerKilled>,
+    mut sounds: EventWriter<PlaySound>,
+    enemy_query: Query<(&Transform, &Enemy)>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let mut handled: HashSet<Entity> = HashSet::new();
+
+    for death in deaths.iter() {
+        if !handled.insert(death.entity) {
+            continue;
+        }
+        if let Ok((tf, enemy)) = enemy_query.get(death.entity) {
+            active_enemies.0 = active_enemies.0.saturating_sub(1);
+            if death.scored {
+                enemy_killed.send(EnemyKilled {
+                    kind: enemy.kind.clone(),
+                    position: tf.translation,
+                });
+            }
+            commands.spawn().insert(ExplosionToSpawn(tf.translation));
+        } else if let Ok(tf) = player_query.get(death.entity) {
+            player_state.shot(time.seconds_since_startup());
+            player_killed.send(PlayerKilled);
+            sounds.send(PlaySound("player_hit"));
+            commands.spawn().insert(ExplosionToSpawn(tf.translation));
+        } else {
+            // despawned by a restart in the meantime
+            continue;
+        }
+        despawns.send(DespawnEvent(death.entity));
+    }
+}
+
+fn despawn_once(mut commands: Commands, mut despawns: EventReader<DespawnEvent>) {
+    let mut despawned: HashSet<Entity> = HashSet::new();
+    for DespawnEvent(entity) in despawns.iter() {
+        if despawned.insert(*entity) {
+            commands.entity(*entity).despawn();
+        }
+    }
+}
@@ -1,143 +1,139 @@
 use std::f32::consts::PI;
 
-use bevy::{core::FixedTimestep, prelude::*};
-use rand::{thread_rng, Rng};
+use bevy::prelude::*;
+use rand::Rng;
+use rust_common::menu::not_paused;
 
 use crate::{
-    ActiveEnemies, Enemy, FromEnemy, Laser, Materials, Speed, WinSize, MAX_ENEMIES,
-    MAX_FORMATION_MEMBERS, SCALE, TIME_STEPS,
+    archetype::{EnemyBook, EnemyLooks, FirePattern, Movement},
+    damage::DespawnEvent,
+    formation::{Formation, FormationBook, FormationMaker},
+    waves::{Wave, WaveStage},
+    ActiveEnemies, Enemy, FromEnemy, GamePhase, GameRng, Health, Laser, Materials, Player, Speed,
+    TimeStep, Velocity, WinSize, SCALE,
 };
 
-// region: Formation
-// Component
-#[derive(Default, Clone)]
-struct Formation {
-    start: (f32, f32),
-    radius: (f32, f32),
-    offset: (f32, f32),
-    angle: f32,
-    group_id: u32,
+// region: Components
+/// Time until a diving enemy leaves its formation.
+struct DiveTimer(Timer);
+/// The enemy left its formation to crash into the player.
+pub struct Diving {
+    velocity: Vec2,
 }
-
-// Resource
-#[derive(Default)]
-struct FormationMaker {
-    group_seq: u32,
-    current_formation: Option<Formation>,
-    current_formation_members: u32,
-}
-
-impl FormationMaker {
-    fn make(&mut self, win_size: &WinSize) -> Formation {
-        match (
-            &self.current_formation,
-            self.current_formation_members >= MAX_FORMATION_MEMBERS,
-        ) {
-            (None, _) | (_, true) => {
-                let mut rng = thread_rng();
-                let h_span = win_size.height / 2.0 - 100.0;
-                let w_span = win_size.width / 2.0 - 100.0;
-                let x = if rng.gen::<bool>() {
-                    win_size.width
-                } else {
-                    -win_size.width
-                };
-                let y = rng.gen_range(-h_span..h_span) as f32;
-                let start = (x, y);
-
-                let offset = (rng.gen_range(-w_span..w_span), rng.gen_range(0.0..h_span));
-                let radius = (rng.gen_range(80.0..150.0), 100.0);
-                let angle: f32 = (y - offset.0).atan2(x - offset.1);
-
-                self.group_seq += 1;
-                let group_id = self.group_seq;
-                let formation = Formation {
-                    start,
-                    offset,
-                    radius,
-                    angle,
-                    group_id,
-                };
-
-                self.current_formation = Some(formation.clone());
-                self.current_formation_members = 1;
-                formation
-            }
-            (Some(tmpl), false) => {
-                self.current_formation_members += 1;
-                tmpl.clone()
-            }
-        }
-    }
+/// Time until the enemy fires again.
+struct FireController(Timer);
+/// The missile steers after the player until the timer runs out.
+struct Homing {
+    turn_rate: f32,
+    timer: Timer,
 }
-// endregion: Formation
+// endregion: Components
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(FormationMaker::default())
-            .add_system(enemy_laser_movement.system())
-            .add_system(enemy_movement.system())
+        app.insert_resource(FormationBook::load())
+            .insert_resource(FormationMaker::default())
             .add_system_set(
                 SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(1.0))
-                    .with_system(enemy_spawn.system()),
-            )
-            .add_system_set(
-                SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(0.9))
+                    .with_run_criteria(not_paused.system())
+                    .with_system(enemy_laser_movement.system().label("movement"))
+                    .with_system(homing_missiles.system())
+                    .with_system(enemy_movement.system().label("movement"))
+                    .with_system(enemy_dive.system())
+                    .with_system(diver_movement.system().label("movement"))
+                    .with_system(enemy_spawn.system())
                     .with_system(enemy_fire.system()),
             );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn enemy_spawn(
     mut commands: Commands,
     mut active_enemies: ResMut<ActiveEnemies>,
     mut formation_maker: ResMut<FormationMaker>,
+    formation_book: Res<FormationBook>,
+    mut rng: ResMut<GameRng>,
+    mut wave: ResMut<Wave>,
+    time: Res<Time>,
     win_size: Res<WinSize>,
-    materials: Res<Materials>,
+    book: Res<EnemyBook>,
+    looks: Res<EnemyLooks>,
+    phase: Res<GamePhase>,
 ) {
-    // Compute random enemy position
-    let formation = formation_maker.make(&win_size);
-    let (x, y) = formation.start;
+    if !phase.playing() {
+        return;
+    }
+
+    let spawned = wave.spawned;
+    let (def, spawn_timer) = match &mut wave.stage {
+        WaveStage::Fighting {
+            def, spawn_timer, ..
+        } => (def, spawn_timer),
+        _ => return,
+    };
+    if !spawn_timer.tick(time.delta()).just_finished() {
+        return;
+    }
 
     //  spawn enemy
-    if active_enemies.0 < MAX_ENEMIES {
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.enemy_materials.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y, 10.0),
-                    scale: Vec3::new(SCALE, SCALE, 1.0),
-                    ..Default::default()
-                },
+    if spawned < def.enemies && active_enemies.0 < def.max_active {
+        // Compute random enemy position
+        let formation = formation_maker.make(
+            &formation_book,
+            &def.formations,
+            &win_size,
+            def.group_size,
+            &mut rng.rng,
+        );
+        let (x, y) = (formation.start.x, formation.start.y);
+        let kind = def.kinds[spawned as usize % def.kinds.len()].clone();
+        let enemy = book.get(&kind);
+        let speed = def.enemy_speed * enemy.speed;
+        let scale = SCALE * enemy.scale;
+
+        let mut entity = commands.spawn_bundle(SpriteBundle {
+            material: looks.material(&kind),
+            transform: Transform {
+                translation: Vec3::new(x, y, 10.0),
+                scale: Vec3::new(scale, scale, 1.0),
                 ..Default::default()
-            })
-            .insert(Enemy)
-            .insert(Speed::default())
+            },
+            ..Default::default()
+        });
+        entity
+            .insert(Health(enemy.hp))
+            .insert(Speed(speed))
             .insert(formation);
+        if let Movement::Dive { after, .. } = enemy.movement {
+            entity.insert(DiveTimer(Timer::from_seconds(after, false)));
+        }
+        if !matches!(enemy.fire, FirePattern::Never) {
+            let cooldown = fire_cooldown(def.fire_interval * enemy.cooldown, &mut rng.rng);
+            entity.insert(FireController(Timer::from_seconds(cooldown, false)));
+        }
+        entity.insert(Enemy { kind });
 
-        active_enemies.0 += 1
+        active_enemies.0 += 1;
+        wave.spawned += 1;
     }
 }
 
-fn enemy_movement(mut query: Query<(&mut Transform, &Speed, &mut Formation), With<Enemy>>) {
+pub fn enemy_movement(
+    step: Res<TimeStep>,
+    win_size: Res<WinSize>,
+    mut query: Query<(&mut Transform, &Speed, &mut Formation), (With<Enemy>, Without<Diving>)>,
+) {
     for (mut tf, speed, mut formation) in query.iter_mut() {
-        let max_distance = TIME_STEPS * speed.0;
+        let max_distance = step.0 * speed.0;
         let x_org = tf.translation.x;
         let y_org = tf.translation.y;
 
-        let (x_offset, y_offset) = formation.offset;
-        let (x_radius, y_radius) = formation.radius;
-
-        let dir = if formation.start.0 > 0.0 { 1.0 } else { -1.0 };
-        let angle =
-            formation.angle + dir * speed.0 * TIME_STEPS / (x_radius.min(y_radius) * PI / 2.0);
-
-        let x_dst = x_radius * angle.cos() + x_offset;
-        let y_dst = y_radius * angle.sin() + y_offset;
+        let progress = formation.progress + max_distance;
+        let dst = formation.point(progress, &win_size);
+        let (x_dst, y_dst) = (dst.x, dst.y);
 
         let dx = x_org - x_dst;
         let dy = y_org - y_dst;
@@ -153,8 +149,10 @@ fn enemy_movement(mut query: Query<(&mut Transform, &Speed, &mut Formation), Wit
         let y = y_org - dy * distance_ratio;
         let y = if dy > 0.0 { y.max(y_dst) } else { y.min(y_dst) };
 
-        if distance < max_distance * speed.0 / 20.0 {
-            formation.angle = angle;
+        // only move along the formation once it is joined, faster enemies
+        // join from further away
+        if distance < speed.0 * speed.0 / 1200.0 {
+            formation.progress = progress;
         }
 
         tf.translation.x = x;
@@ -162,42 +160,246 @@ fn enemy_movement(mut query: Query<(&mut Transform, &Speed, &mut Formation), Wit
     }
 }
 
+fn enemy_dive(
+    mut commands: Commands,
+    time: Res<Time>,
+    book: Res<EnemyBook>,
+    phase: Res<GamePhase>,
+    player_query: Query<&Transform, With<Player>>,
+    mut query: Query<(Entity, &Enemy, &Transform, &mut DiveTimer), Without<Diving>>,
+) {
+    if !phase.playing() {
+        return;
+    }
+
+    for (entity, enemy, tf, mut timer) in query.iter_mut() {
+        let speed = match book.get(&enemy.kind).movement {
+            Movement::Dive { speed, .. } => speed,
+            Movement::Formation => continue,
+        };
+        if !timer.0.tick(time.delta()).finished() {
+            continue;
+        }
+
+        // aim where the player is now, or straight down without one
+        let position = Vec2::from(tf.translation);
+        let direction = player_query
+            .single()
+            .map(|player_tf| (Vec2::from(player_tf.translation) - position).normalize_or_zero())
+            .ok()
+            .filter(|direction| *direction != Vec2::ZERO)
+            .unwrap_or(-Vec2::Y);
+        commands.entity(entity).insert(Diving {
+            velocity: direction * speed,
+        });
+    }
+}
+
+fn diver_movement(
+    mut commands: Commands,
+    step: Res<TimeStep>,
+    win_size: Res<WinSize>,
+    mut query: Query<(Entity, &mut Transform, &Diving, &mut DiveTimer)>,
+) {
+    for (entity, mut tf, diving, mut timer) in query.iter_mut() {
+        tf.translation += (diving.velocity * step.0).extend(0.0);
+
+        // past the bottom or a side, come back from the top and rejoin
+        let (x, y) = (tf.translation.x, tf.translation.y);
+        if y < -win_size.height / 2.0 - 50.0 || x.abs() > win_size.width / 2.0 + 50.0 {
+            tf.translation.x = x.clamp(-win_size.width / 2.0, win_size.width / 2.0);
+            tf.translation.y = win_size.height / 2.0 + 50.0;
+            timer.0.reset();
+            commands.entity(entity).remove::<Diving>();
+        }
+    }
+}
+
+/// Seconds until the next volley, randomized around `interval` so enemies
+/// don't fire in lockstep.
+pub fn fire_cooldown(interval: f32, rng: &mut impl Rng) -> f32 {
+    interval * rng.gen_range(0.5..1.5)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn enemy_fire(
     mut commands: Commands,
+    time: Res<Time>,
+    wave: Res<Wave>,
+    book: Res<EnemyBook>,
     materials: Res<Materials>,
-    enemy_query: Query<&Transform, With<Enemy>>,
+    phase: Res<GamePhase>,
+    mut rng: ResMut<GameRng>,
+    player_query: Query<&Transform, With<Player>>,
+    mut enemy_query: Query<(&Transform, &Enemy, &mut FireController)>,
 ) {
-    for &tf in enemy_query.iter() {
-        let x = tf.translation.x;
-        let y = tf.translation.y;
-
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.enemy_laser_materials.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y - 15.0, 0.0),
-                    scale: Vec3::new(SCALE, -SCALE, 1.0),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Laser)
-            .insert(FromEnemy)
-            .insert(Speed::default());
+    if !phase.playing() {
+        return;
+    }
+    let interval = match &wave.stage {
+        WaveStage::Fighting { def, .. } => def.fire_interval,
+        _ => return,
+    };
+    let player = player_query
+        .single()
+        .ok()
+        .map(|tf| Vec2::from(tf.translation));
+
+    for (&tf, enemy, mut controller) in enemy_query.iter_mut() {
+        if !controller.0.tick(time.delta()).finished() {
+            continue;
+        }
+        let enemy = book.get(&enemy.kind);
+        let cooldown = fire_cooldown(interval * enemy.cooldown, &mut rng.rng);
+        controller.0 = Timer::from_seconds(cooldown, false);
+
+        let origin = Vec2::new(tf.translation.x, tf.translation.y - 15.0);
+        fire_pattern(&mut commands, &materials, enemy.fire, origin, player);
     }
 }
 
-fn enemy_laser_movement(
+/// Fires one volley of `pattern` from `origin`, aiming at `player` if
+/// there is one.
+pub fn fire_pattern(
+    commands: &mut Commands,
+    materials: &Materials,
+    pattern: FirePattern,
+    origin: Vec2,
+    player: Option<Vec2>,
+) {
+    let speed = Speed::default().0;
+    let down = -Vec2::Y;
+    let aim = player
+        .map(|player| (player - origin).normalize_or_zero())
+        .filter(|direction| *direction != Vec2::ZERO)
+        .unwrap_or(down);
+    let mut fire = |velocity: Vec2| spawn_enemy_laser(commands, materials, origin, velocity);
+
+    match pattern {
+        FirePattern::Never => {}
+        FirePattern::Straight => {
+            fire(down * speed);
+        }
+        FirePattern::Aimed => {
+            fire(aim * speed);
+        }
+        FirePattern::Spread { count, angle } => {
+            let count = count.max(1);
+            let step = if count > 1 {
+                angle.to_radians() / (count - 1) as f32
+            } else {
+                0.0
+            };
+            let first = -step * (count - 1) as f32 / 2.0;
+            for i in 0..count {
+                fire(rotate(down, first + step * i as f32) * speed);
+            }
+        }
+        FirePattern::Radial { count } => {
+            let count = count.max(1);
+            for i in 0..count {
+                fire(rotate(down, 2.0 * PI * i as f32 / count as f32) * speed);
+            }
+        }
+        FirePattern::Homing {
+            speed,
+            turn_rate,
+            lifetime,
+        } => {
+            let missile = fire(aim * speed);
+            commands.entity(missile).insert(Homing {
+                turn_rate: turn_rate.to_radians(),
+                timer: Timer::from_seconds(lifetime, false),
+            });
+        }
+    }
+}
+
+fn rotate(direction: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(
+        direction.x * cos - direction.y * sin,
+        direction.x * sin + direction.y * cos,
+    )
+}
+
+/// Sprite rotation of a laser flying along `velocity`. The sprite points up.
+fn laser_rotation(velocity: Vec2) -> Quat {
+    Quat::from_rotation_z(velocity.y.atan2(velocity.x) - PI / 2.0)
+}
+
+fn spawn_enemy_laser(
+    commands: &mut Commands,
+    materials: &Materials,
+    origin: Vec2,
+    velocity: Vec2,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.enemy_laser_materials.clone(),
+            transform: Transform {
+                translation: origin.extend(0.0),
+                rotation: laser_rotation(velocity),
+                scale: Vec3::new(SCALE, SCALE, 1.0),
+            },
+            ..Default::default()
+        })
+        .insert(Laser)
+        .insert(FromEnemy)
+        .insert(Velocity(velocity))
+        .id()
+}
+
+pub fn enemy_laser_movement(
+    step: Res<TimeStep>,
     window: Res<WinSize>,
-    mut commands: Commands,
-    mut query: Query<(Entity, &Speed, &mut Transform), (With<Laser>, With<FromEnemy>)>,
+    mut despawns: EventWriter<DespawnEvent>,
+    mut query: Query<(Entity, &Velocity, &mut Transform), (With<Laser>, With<FromEnemy>)>,
 ) {
-    for (enemy_laser_entity, speed, mut laser_tf) in query.iter_mut() {
+    for (enemy_laser_entity, velocity, mut laser_tf) in query.iter_mut() {
         let translation = &mut laser_tf.translation;
-        translation.y -= speed.0 * TIME_STEPS;
+        *translation += (velocity.0 * step.0).extend(0.0);
+
+        if translation.y < -window.height / 2.0 - 50.0
+            || translation.y > window.height / 2.0 + 50.0
+            || translation.x.abs() > window.width / 2.0 + 50.0
+        {
+            despawns.send(DespawnEvent(enemy_laser_entity));
+        }
+    }
+}
+
+fn homing_missiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    step: Res<TimeStep>,
+    player_query: Query<&Transform, With<Player>>,
+    mut query: Query<(Entity, &mut Velocity, &mut Transform, &mut Homing), Without<Player>>,
+) {
+    let player = player_query
+        .single()
+        .ok()
+        .map(|tf| Vec2::from(tf.translation));
+
+    for (entity, mut velocity, mut tf, mut homing) in query.iter_mut() {
+        if homing.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Homing>();
+            continue;
+        }
+        let player = match player {
+            Some(player) => player,
+            None => continue,
+        };
 
-        if translation.y < -window.height / 2.0 - 50.0 {
-            commands.entity(enemy_laser_entity).despawn();
+        // turn towards the player, no faster than the turn rate
+        let to_player = player - Vec2::from(tf.translation);
+        let turn = velocity
+            .0
+            .angle_between(to_player)
+            .clamp(-homing.turn_rate * step.0, homing.turn_rate * step.0);
+        if turn.is_finite() {
+            velocity.0 = rotate(velocity.0, turn);
+            tf.rotation = laser_rotation(velocity.0);
         }
     }
 }
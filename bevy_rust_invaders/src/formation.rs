@@ -0,0 +1,336 @@
+use std::{collections::BTreeMap, f32::consts::PI, fs};
+
+use bevy::prelude::*;
+use rand::Rng;
+use rust_common::asset_path;
+use serde::Deserialize;
+
+use crate::{waves::check_positive, WinSize};
+
+const FORMATIONS_FILE: &str = "formations.ron";
+/// Distance kept between the formations and the window sides.
+const MARGIN: f32 = 100.0;
+/// Samples measuring the length of a path.
+const PATH_SAMPLES: usize = 16;
+
+// region: Definitions
+/// A way for a group of enemies to fly, loaded from `assets/formations.ron`.
+/// Distances are in pixels; `gap` is how far each member trails the one
+/// spawned before it.
+#[derive(Deserialize, Clone, Debug)]
+pub enum FormationKind {
+    /// Circles an ellipse around a random point of the upper half.
+    Orbit { radius: (f32, f32) },
+    /// Marches side to side as a block of `columns`, dropping down at every
+    /// turn until `max_drop`.
+    Grid {
+        columns: u32,
+        spacing: (f32, f32),
+        drop: f32,
+        max_drop: f32,
+    },
+    /// Sweeps side to side as a V behind its leader.
+    Wedge { spacing: (f32, f32) },
+    /// Sweeps side to side along a sine wave.
+    Sine {
+        amplitude: f32,
+        wavelength: f32,
+        gap: f32,
+    },
+    /// Loops a figure eight around a random point of the upper half.
+    FigureEight { radius: (f32, f32), gap: f32 },
+    /// Follows a Bezier curve back and forth. The control points are in
+    /// fractions of half the window, from its center.
+    Path { points: Vec<(f32, f32)>, gap: f32 },
+}
+
+impl FormationKind {
+    fn check(&self) -> Result<(), String> {
+        match self {
+            FormationKind::Orbit { radius } => {
+                check_positive("radius", radius.0)?;
+                check_positive("radius", radius.1)
+            }
+            FormationKind::Grid {
+                columns, spacing, ..
+            } => {
+                if *columns == 0 {
+                    return Err("columns has to be above zero".to_string());
+                }
+                check_positive("spacing", spacing.0)?;
+                check_positive("spacing", spacing.1)
+            }
+            FormationKind::Wedge { spacing } => {
+                check_positive("spacing", spacing.0)?;
+                check_positive("spacing", spacing.1)
+            }
+            FormationKind::Sine {
+                wavelength, gap, ..
+            } => {
+                check_positive("wavelength", *wavelength)?;
+                check_positive("gap", *gap)
+            }
+            FormationKind::FigureEight { radius, gap } => {
+                check_positive("radius", radius.0)?;
+                check_positive("radius", radius.1)?;
+                check_positive("gap", *gap)
+            }
+            FormationKind::Path { points, gap } => {
+                if points.len() < 2 {
+                    return Err("a path needs at least two points".to_string());
+                }
+                check_positive("gap", *gap)
+            }
+        }
+    }
+}
+
+// Resource
+/// Formations by the name waves refer to them with.
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct FormationBook(BTreeMap<String, FormationKind>);
+
+impl Default for FormationBook {
+    /// The orbit the game flew before formations were data.
+    fn default() -> Self {
+        let mut formations = BTreeMap::new();
+        formations.insert(
+            "orbit".to_string(),
+            FormationKind::Orbit {
+                radius: (120.0, 100.0),
+            },
+        );
+        Self(formations)
+    }
+}
+
+impl FormationBook {
+    pub fn load() -> Self {
+        fs::read_to_string(asset_path(FORMATIONS_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str::<Self>(&content).map_err(|e| e.to_string()))
+            .and_then(|book| {
+                if book.0.is_empty() {
+                    return Err("no formations defined".to_string());
+                }
+                for (name, kind) in book.0.iter() {
+                    kind.check().map_err(|e| format!("{}: {}", name, e))?;
+                }
+                Ok(book)
+            })
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", FORMATIONS_FILE, e);
+                Self::default()
+            })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+}
+// endregion: Definitions
+
+// region: Formation
+// Component
+#[derive(Clone)]
+pub struct Formation {
+    kind: FormationKind,
+    /// Center of orbits and figure eights, or the height of sweeps.
+    anchor: Vec2,
+    /// 1.0 when the group enters from the left, -1.0 from the right.
+    mirror: f32,
+    /// Place of the enemy within its group.
+    slot: u32,
+    /// Distance flown along the formation.
+    pub progress: f32,
+    pub start: Vec2,
+    /// Curve of `Path` formations, laid out once for the window.
+    curve: Curve,
+}
+
+impl Formation {
+    /// Where the formation wants the enemy once it flew `progress`.
+    pub fn point(&self, progress: f32, win_size: &WinSize) -> Vec2 {
+        let half = Vec2::new(win_size.width, win_size.height) / 2.0;
+        let sweep = 2.0 * (half.x - MARGIN);
+        let slot = self.slot as f32;
+        let trail = |gap: f32| (progress - slot * gap).max(0.0);
+        let side = |x: f32| self.mirror * x;
+
+        match &self.kind {
+            FormationKind::Orbit { radius } => {
+                let angle = self.mirror * progress / (radius.0.min(radius.1) * PI / 2.0);
+                self.anchor + Vec2::new(radius.0 * angle.cos(), radius.1 * angle.sin())
+            }
+            FormationKind::Grid {
+                columns,
+                spacing,
+                drop,
+                max_drop,
+            } => {
+                let columns = (*columns).max(1);
+                let (column, row) = ((self.slot % columns) as f32, (self.slot / columns) as f32);
+                let span = (sweep - (columns - 1) as f32 * spacing.0).max(1.0);
+                let turns = (progress / span).floor();
+                Vec2::new(
+                    side(-half.x + MARGIN + bounce(progress, span) + column * spacing.0),
+                    self.anchor.y - row * spacing.1 - (turns * drop).min(*max_drop),
+                )
+            }
+            FormationKind::Wedge { spacing } => {
+                let rank = ((self.slot + 1) / 2) as f32;
+                let wing = if self.slot % 2 == 1 { -1.0 } else { 1.0 };
+                Vec2::new(
+                    side(-half.x + MARGIN + bounce(progress, sweep) + wing * rank * spacing.0),
+                    self.anchor.y + rank * spacing.1,
+                )
+            }
+            FormationKind::Sine {
+                amplitude,
+                wavelength,
+                gap,
+            } => {
+                let progress = trail(*gap);
+                Vec2::new(
+                    side(-half.x + MARGIN + bounce(progress, sweep)),
+                    self.anchor.y + amplitude * (2.0 * PI * progress / wavelength).sin(),
+                )
+            }
+            FormationKind::FigureEight { radius, gap } => {
+                let angle = trail(*gap) / radius.0;
+                self.anchor
+                    + Vec2::new(side(radius.0 * angle.sin()), radius.1 * (2.0 * angle).sin())
+            }
+            FormationKind::Path { gap, .. } => self.curve.at(trail(*gap)),
+        }
+    }
+}
+
+/// Bezier curve in window coordinates, with its length measured up front.
+#[derive(Clone, Default)]
+struct Curve {
+    points: Vec<Vec2>,
+    length: f32,
+}
+
+impl Curve {
+    fn new(kind: &FormationKind, mirror: f32, win_size: &WinSize) -> Self {
+        let points: Vec<Vec2> = match kind {
+            FormationKind::Path { points, .. } => points
+                .iter()
+                .map(|&(x, y)| Vec2::new(mirror * x * win_size.width, y * win_size.height) / 2.0)
+                .collect(),
+            _ => return Self::default(),
+        };
+        let length = (1..=PATH_SAMPLES)
+            .map(|i| {
+                let t = i as f32 / PATH_SAMPLES as f32;
+                bezier(&points, t).distance(bezier(&points, t - 1.0 / PATH_SAMPLES as f32))
+            })
+            .sum::<f32>()
+            .max(1.0);
+        Self { points, length }
+    }
+
+    /// Point of the curve once `distance` was flown back and forth along it.
+    fn at(&self, distance: f32) -> Vec2 {
+        bezier(&self.points, bounce(distance, self.length) / self.length)
+    }
+}
+
+/// Goes from 0 to `span` and back as `distance` grows.
+fn bounce(distance: f32, span: f32) -> f32 {
+    let distance = distance.rem_euclid(2.0 * span);
+    if distance > span {
+        2.0 * span - distance
+    } else {
+        distance
+    }
+}
+
+/// Point `t` (0 to 1) of the Bezier curve with control `points`.
+fn bezier(points: &[Vec2], t: f32) -> Vec2 {
+    // Bernstein form, which needs no scratch points
+    let degree = points.len().saturating_sub(1) as i32;
+    let mut binomial = 1.0;
+    let mut sum = Vec2::ZERO;
+    for (i, point) in (0..).zip(points) {
+        sum += *point * binomial * t.powi(i) * (1.0 - t).powi(degree - i);
+        binomial *= (degree - i) as f32 / (i + 1) as f32;
+    }
+    sum
+}
+
+// Resource
+#[derive(Default)]
+pub struct FormationMaker {
+    current_formation: Option<Formation>,
+    current_formation_members: u32,
+}
+
+impl FormationMaker {
+    /// Formation of the next enemy. Every `group_size` enemies a new group
+    /// starts, flying one of `names`, or any formation if none of them is known.
+    pub fn make(
+        &mut self,
+        book: &FormationBook,
+        names: &[String],
+        win_size: &WinSize,
+        group_size: u32,
+        rng: &mut impl Rng,
+    ) -> Formation {
+        match (
+            &self.current_formation,
+            self.current_formation_members >= group_size,
+        ) {
+            (None, _) | (_, true) => {
+                let mut kinds: Vec<&FormationKind> =
+                    names.iter().filter_map(|name| book.0.get(name)).collect();
+                if kinds.is_empty() {
+                    kinds = book.0.values().collect();
+                }
+                let kind = kinds[rng.gen_range(0..kinds.len())].clone();
+
+                let h_span = win_size.height / 2.0 - MARGIN;
+                let w_span = win_size.width / 2.0 - MARGIN;
+                let mirror = if rng.gen::<bool>() { 1.0 } else { -1.0 };
+                let anchor = Vec2::new(rng.gen_range(-w_span..w_span), rng.gen_range(0.0..h_span));
+
+                let mut formation = Formation {
+                    curve: Curve::new(&kind, mirror, win_size),
+                    kind,
+                    anchor,
+                    mirror,
+                    slot: 0,
+                    progress: 0.0,
+                    start: Vec2::ZERO,
+                };
+                formation.start = match formation.kind {
+                    FormationKind::Orbit { radius } => {
+                        // enter anywhere along a side and join the orbit
+                        // where it is closest
+                        let start =
+                            Vec2::new(mirror * win_size.width, rng.gen_range(-h_span..h_span));
+                        let to_start = start - anchor;
+                        let angle = to_start.y.atan2(to_start.x);
+                        formation.progress = mirror * angle * radius.0.min(radius.1) * PI / 2.0;
+                        start
+                    }
+                    _ => Vec2::new(-mirror * win_size.width, formation.point(0.0, win_size).y),
+                };
+
+                self.current_formation = Some(formation.clone());
+                self.current_formation_members = 1;
+                formation
+            }
+            (Some(tmpl), false) => {
+                let mut formation = tmpl.clone();
+                formation.slot = self.current_formation_members;
+                self.current_formation_members += 1;
+                formation
+            }
+        }
+    }
+}
+// endregion: Formation
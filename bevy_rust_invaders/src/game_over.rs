@@ -0,0 +1,220 @@
+use bevy::prelude::*;
+use rust_common::{
+    input::{Action, ActionState},
+    menu::not_paused,
+};
+
+use crate::{
+    boss::Boss,
+    bunker::Bunker,
+    daily::DailyRun,
+    damage::DespawnEvent,
+    powerup::{PowerUp, PowerUps},
+    score::{HiScore, Score},
+    waves::Wave,
+    ActiveEnemies, Enemy, GameEnded, GamePhase, GameRng, Laser, LifeRules, PlayerKilled,
+    PlayerState, FONT,
+};
+
+const CONTINUE_SECONDS: f32 = 10.0;
+
+// region: Components
+struct ResultsScreen;
+// endregion: Components
+
+/// Ends the game once the last life is lost: first a countdown to continue
+/// while continues are left, then the results until Fire starts a new game.
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(GamePhase::Playing).add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(out_of_lives.system())
+                .with_system(continue_countdown.system())
+                .with_system(restart.system())
+                .with_system(draw_results.system()),
+        );
+    }
+}
+
+fn out_of_lives(
+    mut player_killed: EventReader<PlayerKilled>,
+    mut game_ended: EventWriter<GameEnded>,
+    mut phase: ResMut<GamePhase>,
+    player_state: Res<PlayerState>,
+    score: Res<Score>,
+    mut hi_score: ResMut<HiScore>,
+    daily: Option<Res<DailyRun>>,
+) {
+    if player_killed.iter().next().is_none() || player_state.lives > 0 || !phase.playing() {
+        return;
+    }
+
+    // continuing starts the score over, so this is the run's final score
+    if daily.is_none() {
+        hi_score.submit(score.points);
+    }
+    if player_state.continues > 0 {
+        *phase = GamePhase::Continue(Timer::from_seconds(CONTINUE_SECONDS, false));
+    } else {
+        *phase = GamePhase::GameOver;
+        game_ended.send(GameEnded);
+    }
+}
+
+fn continue_countdown(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    rules: Res<LifeRules>,
+    mut phase: ResMut<GamePhase>,
+    mut player_state: ResMut<PlayerState>,
+    mut score: ResMut<Score>,
+    mut game_ended: EventWriter<GameEnded>,
+) {
+    let timer = match &mut *phase {
+        GamePhase::Continue(timer) => timer,
+        _ => return,
+    };
+
+    if actions.just_pressed(Action::Fire) {
+        player_state.continues -= 1;
+        player_state.lives = rules.lives;
+        *score = Score::default();
+        *phase = GamePhase::Playing;
+    } else if timer.tick(time.delta()).finished() {
+        *phase = GamePhase::GameOver;
+        game_ended.send(GameEnded);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restart(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    rules: Res<LifeRules>,
+    mut phase: ResMut<GamePhase>,
+    mut player_state: ResMut<PlayerState>,
+    mut score: ResMut<Score>,
+    mut wave: ResMut<Wave>,
+    mut power_ups: ResMut<PowerUps>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    mut rng: ResMut<GameRng>,
+    daily: Option<Res<DailyRun>>,
+    mut despawns: EventWriter<DespawnEvent>,
+    leftovers: Query<Entity, Or<(With<Enemy>, With<Laser>, With<Boss>, With<Bunker>)>>,
+    pickups: Query<Entity, With<PowerUp>>,
+) {
+    if !matches!(*phase, GamePhase::GameOver) || !actions.just_pressed(Action::Fire) {
+        return;
+    }
+
+    // after the update, the other systems may still insert components into
+    // them this frame
+    for entity in leftovers.iter() {
+        despawns.send(DespawnEvent(entity));
+    }
+    for entity in pickups.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    active_enemies.0 = 0;
+    *power_ups = PowerUps::default();
+    *player_state = PlayerState::new_game(&rules);
+    *score = Score::default();
+    *wave = Wave::default();
+    // daily retries replay the same waves and reseed on their own
+    if daily.is_none() {
+        *rng = GameRng::default();
+    }
+    *phase = GamePhase::Playing;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_results(
+    mut commands: Commands,
+    mut shown: Local<String>,
+    phase: Res<GamePhase>,
+    player_state: Res<PlayerState>,
+    score: Res<Score>,
+    hi_score: Res<HiScore>,
+    wave: Res<Wave>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    screens: Query<Entity, With<ResultsScreen>>,
+) {
+    let (title, lines) = match &*phase {
+        GamePhase::Playing => (String::new(), Vec::new()),
+        GamePhase::Continue(timer) => (
+            format!(
+                "CONTINUE?  {}",
+                (timer.duration().as_secs_f32() - timer.elapsed_secs()).ceil()
+            ),
+            vec![format!("Fire: continue ({} left)", player_state.continues)],
+        ),
+        GamePhase::GameOver => (
+            "GAME OVER".to_string(),
+            vec![
+                format!("Score  {}", score.points),
+                format!("Hi-score  {}", hi_score.score.max(score.points)),
+                format!("Wave  {}", wave.number),
+                format!("Enemies destroyed  {}", score.kills),
+                String::new(),
+                "Fire: play again".to_string(),
+            ],
+        ),
+    };
+    let content = format!("{}\n{}", title, lines.join("\n"));
+    if *shown == content {
+        return;
+    }
+    *shown = content;
+
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if title.is_empty() {
+        return;
+    }
+
+    let font = asset_server.load(FONT);
+    let style = |font_size| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    let mut sections = vec![TextSection {
+        value: format!("{}\n\n", title),
+        style: style(32.0),
+    }];
+    sections.extend(lines.into_iter().map(|line| TextSection {
+        value: format!("{}\n", line),
+        style: style(18.0),
+    }));
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.6).into()),
+            ..Default::default()
+        })
+        .insert(ResultsScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections,
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..Default::default()
+                    },
+                },
+                ..Default::default()
+            });
+        });
+}
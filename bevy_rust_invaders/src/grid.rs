@@ -0,0 +1,200 @@
+//! A uniform grid over the playfield for the broad phase of collisions.
+//! Items are filed by the cell of their center, and a query looks through
+//! the cells its box covers, grown by the half size of the largest item, so
+//! no item is ever missed or returned twice.
+
+/// Center and size of an axis-aligned box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub center: (f32, f32),
+    pub size: (f32, f32),
+}
+
+impl Aabb {
+    pub fn new(center: (f32, f32), size: (f32, f32)) -> Self {
+        Self {
+            center,
+            size: (size.0.abs(), size.1.abs()),
+        }
+    }
+
+    /// Whether the boxes overlap; boxes only touching don't.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (self.center.0 - other.center.0).abs() * 2.0 < self.size.0 + other.size.0
+            && (self.center.1 - other.center.1).abs() * 2.0 < self.size.1 + other.size.1
+    }
+}
+
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    /// Corner of the covered area with the lowest coordinates.
+    origin: (f32, f32),
+    columns: i32,
+    rows: i32,
+    /// Row by row, items outside the covered area are filed in the
+    /// nearest edge cell.
+    cells: Vec<Vec<(T, Aabb)>>,
+    /// Half size of the largest item filed since the last clear.
+    reach: (f32, f32),
+    len: usize,
+}
+
+impl<T: Copy> SpatialGrid<T> {
+    /// A grid covering `width` by `height` around the origin.
+    pub fn new(cell_size: f32, width: f32, height: f32) -> Self {
+        let cell_size = cell_size.max(1.0);
+        let columns = (width / cell_size).ceil().max(1.0) as i32;
+        let rows = (height / cell_size).ceil().max(1.0) as i32;
+        Self {
+            cell_size,
+            origin: (-width / 2.0, -height / 2.0),
+            columns,
+            rows,
+            cells: (0..columns * rows).map(|_| Vec::new()).collect(),
+            reach: (0.0, 0.0),
+            len: 0,
+        }
+    }
+
+    /// Empties the grid, keeping the memory of its cells for the next frame.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.reach = (0.0, 0.0);
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, item: T, aabb: Aabb) {
+        self.reach.0 = self.reach.0.max(aabb.size.0 / 2.0);
+        self.reach.1 = self.reach.1.max(aabb.size.1 / 2.0);
+        self.len += 1;
+        let (column, row) = self.cell(aabb.center.0, aabb.center.1);
+        self.cells[(row * self.columns + column) as usize].push((item, aabb));
+    }
+
+    /// Items whose box overlaps `aabb`.
+    pub fn query(&self, aabb: Aabb) -> impl Iterator<Item = (T, Aabb)> + '_ {
+        let half = (
+            aabb.size.0 / 2.0 + self.reach.0,
+            aabb.size.1 / 2.0 + self.reach.1,
+        );
+        let (x0, y0) = self.cell(aabb.center.0 - half.0, aabb.center.1 - half.1);
+        let (x1, y1) = self.cell(aabb.center.0 + half.0, aabb.center.1 + half.1);
+        (y0..=y1)
+            .flat_map(move |row| {
+                let start = (row * self.columns) as usize;
+                self.cells[start + x0 as usize..=start + x1 as usize].iter()
+            })
+            .flatten()
+            .filter(move |(_, other)| other.overlaps(&aabb))
+            .copied()
+    }
+
+    /// Column and row of the cell a point falls in, or of the nearest one.
+    fn cell(&self, x: f32, y: f32) -> (i32, i32) {
+        let column = ((x - self.origin.0) / self.cell_size).floor() as i32;
+        let row = ((y - self.origin.1) / self.cell_size).floor() as i32;
+        (
+            column.clamp(0, self.columns - 1),
+            row.clamp(0, self.rows - 1),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32) -> Aabb {
+        Aabb::new((x, y), (2.0, 2.0))
+    }
+
+    #[test]
+    fn items_outside_the_window_are_filed_in_edge_cells() {
+        let mut grid = SpatialGrid::new(100.0, 200.0, 200.0);
+        assert_eq!(grid.cell(500.0, 0.0), (1, 1));
+        assert_eq!(grid.cell(-300.0, -300.0), (0, 0));
+        assert_eq!(grid.cell(-300.0, 900.0), (0, 1));
+
+        grid.insert(1, point(500.0, 0.0));
+        grid.insert(2, point(-300.0, -300.0));
+        assert_eq!(grid.len(), 2);
+        let found: Vec<u32> = grid.query(point(500.0, 0.0)).map(|(id, _)| id).collect();
+        assert_eq!(found, vec![1]);
+        let found: Vec<u32> = grid
+            .query(point(-300.0, -300.0))
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(found, vec![2]);
+        assert_eq!(grid.query(point(90.0, 0.0)).count(), 0);
+    }
+
+    #[test]
+    fn touching_boxes_do_not_overlap() {
+        let left = Aabb::new((0.0, 0.0), (10.0, 10.0));
+        let touching = Aabb::new((10.0, 0.0), (10.0, 10.0));
+        let overlapping = Aabb::new((9.9, 9.9), (10.0, 10.0));
+        assert!(!left.overlaps(&touching));
+        assert!(!touching.overlaps(&left));
+        assert!(left.overlaps(&overlapping));
+        assert!(Aabb::new((0.0, 0.0), (-10.0, -10.0)).overlaps(&overlapping));
+
+        let mut grid = SpatialGrid::new(10.0, 100.0, 100.0);
+        grid.insert(1, touching);
+        grid.insert(2, overlapping);
+        let found: Vec<u32> = grid.query(left).map(|(id, _)| id).collect();
+        assert_eq!(found, vec![2]);
+    }
+
+    #[test]
+    fn each_item_is_found_once() {
+        let mut grid = SpatialGrid::new(10.0, 100.0, 100.0);
+        let mut id = 0;
+        for x in -6..6 {
+            for y in -6..6 {
+                // some boxes span several cells
+                let size = if (x + y) % 3 == 0 { 25.0 } else { 4.0 };
+                grid.insert(
+                    id,
+                    Aabb::new((x as f32 * 9.0, y as f32 * 9.0), (size, size)),
+                );
+                id += 1;
+            }
+        }
+        let mut found: Vec<u32> = grid
+            .query(Aabb::new((0.0, 0.0), (300.0, 300.0)))
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(found.len(), grid.len());
+        found.sort_unstable();
+        found.dedup();
+        assert_eq!(found.len(), grid.len());
+    }
+
+    #[test]
+    fn reach_grows_with_the_largest_item() {
+        let mut grid = SpatialGrid::new(20.0, 400.0, 400.0);
+        grid.insert(1, point(0.0, 0.0));
+        assert_eq!(grid.reach, (1.0, 1.0));
+
+        // filed far to the left of the cells its right end covers
+        grid.insert(2, Aabb::new((-80.0, 0.0), (300.0, 20.0)));
+        assert_eq!(grid.reach, (150.0, 10.0));
+        let found: Vec<u32> = grid.query(point(60.0, 0.0)).map(|(id, _)| id).collect();
+        assert_eq!(found, vec![2]);
+
+        grid.clear();
+        assert!(grid.is_empty());
+        assert_eq!(grid.reach, (0.0, 0.0));
+        assert_eq!(grid.query(point(60.0, 0.0)).count(), 0);
+    }
+}
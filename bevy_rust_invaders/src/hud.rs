@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use rust_common::menu::not_paused;
+
+use crate::{
+    boss::Boss,
+    powerup::PowerUps,
+    score::{HiScore, Score},
+    waves::Wave,
+    weapon::Weapon,
+    PlayerState, FONT,
+};
+
+// region: Components
+struct ScoreText;
+struct HiScoreText;
+struct LivesText;
+struct WaveText;
+struct PowerUpText;
+struct BossBar;
+struct BossBarFill;
+// endregion: Components
+
+/// A bar along the top of the window with score, hi-score, lives and wave,
+/// below it the hit points of the boss while one is around, and the active
+/// weapon and power-ups in the bottom corner.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(hud_spawn.system()).add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(hud_update.system())
+                .with_system(power_up_indicator.system())
+                .with_system(boss_bar.system()),
+        );
+    }
+}
+
+fn hud_spawn(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let style = TextStyle {
+        font: asset_server.load(FONT),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+    let text = |value: &str| TextBundle {
+        text: Text::with_section(value, style.clone(), Default::default()),
+        ..Default::default()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::SpaceBetween,
+                padding: Rect::all(Val::Px(6.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.4).into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(text("")).insert(ScoreText);
+            parent.spawn_bundle(text("")).insert(HiScoreText);
+            parent.spawn_bundle(text("")).insert(LivesText);
+            parent.spawn_bundle(text("")).insert(WaveText);
+        });
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(6.0),
+                    left: Val::Px(6.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section("", style.clone(), Default::default()),
+            ..Default::default()
+        })
+        .insert(PowerUpText);
+}
+
+fn hud_update(
+    time: Res<Time>,
+    score: Res<Score>,
+    hi_score: Res<HiScore>,
+    player_state: Res<PlayerState>,
+    wave: Res<Wave>,
+    mut texts: QuerySet<(
+        Query<&mut Text, With<ScoreText>>,
+        Query<&mut Text, With<HiScoreText>>,
+        Query<&mut Text, With<LivesText>>,
+        Query<&mut Text, With<WaveText>>,
+    )>,
+) {
+    let combo = if score.combo_running(time.seconds_since_startup()) {
+        format!("  x{}", score.combo)
+    } else {
+        String::new()
+    };
+    let values = [
+        format!("SCORE {:06}{}", score.points, combo),
+        format!("HI {:06}", hi_score.score.max(score.points)),
+        format!("LIVES {}", player_state.lives),
+        format!("WAVE {}", wave.number),
+    ];
+
+    let set = |mut text: Mut<Text>, value: &String| {
+        // only touch the text when it changes, to spare the relayout
+        if text.sections[0].value != *value {
+            text.sections[0].value = value.clone();
+        }
+    };
+    if let Ok(text) = texts.q0_mut().single_mut() {
+        set(text, &values[0]);
+    }
+    if let Ok(text) = texts.q1_mut().single_mut() {
+        set(text, &values[1]);
+    }
+    if let Ok(text) = texts.q2_mut().single_mut() {
+        set(text, &values[2]);
+    }
+    if let Ok(text) = texts.q3_mut().single_mut() {
+        set(text, &values[3]);
+    }
+}
+
+fn power_up_indicator(
+    power_ups: Res<PowerUps>,
+    weapon_query: Query<&Weapon>,
+    mut query: Query<&mut Text, With<PowerUpText>>,
+) {
+    let weapon = weapon_query.single().ok().map(|weapon| {
+        let heat = if weapon.overheated {
+            "  OVERHEAT".to_string()
+        } else if weapon.stats.heat_per_shot > 0.0 {
+            format!("  HEAT {:.0}%", weapon.heat * 100.0)
+        } else {
+            String::new()
+        };
+        format!("LV {}{}", weapon.level + 1, heat)
+    });
+    let power_ups = power_ups
+        .active
+        .iter()
+        .map(|(kind, active)| {
+            let charges = if active.charges > 1 {
+                format!(" x{}", active.charges)
+            } else {
+                String::new()
+            };
+            format!("{}{} {:.0}", kind.name(), charges, active.remaining.ceil())
+        })
+        .collect::<Vec<_>>();
+    let value = weapon
+        .into_iter()
+        .chain(power_ups)
+        .collect::<Vec<_>>()
+        .join("   ");
+
+    if let Ok(mut text) = query.single_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn boss_bar(
+    mut commands: Commands,
+    mut shown: Local<Option<String>>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    boss_query: Query<&Boss>,
+    bar_query: Query<Entity, With<BossBar>>,
+    mut fill_query: Query<&mut Style, With<BossBarFill>>,
+) {
+    let boss = boss_query.iter().next();
+    let name = boss.map(|boss| boss.name.to_uppercase());
+    if *shown != name {
+        *shown = name.clone();
+        for entity in bar_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        if let Some(name) = name {
+            spawn_boss_bar(&mut commands, &asset_server, &mut materials, name);
+        }
+    }
+
+    if let Some(boss) = boss {
+        for mut style in fill_query.iter_mut() {
+            let width = Val::Percent(boss.health.clamp(0.0, 1.0) * 100.0);
+            if style.size.width != width {
+                style.size.width = width;
+            }
+        }
+    }
+}
+
+fn spawn_boss_bar(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    name: String,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(60.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(36.0),
+                    left: Val::Percent(20.0),
+                    ..Default::default()
+                },
+                // children are laid out bottom up
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(BossBar)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    name,
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.6).into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::rgb(0.9, 0.2, 0.2).into()),
+                            ..Default::default()
+                        })
+                        .insert(BossBarFill);
+                });
+        });
+}
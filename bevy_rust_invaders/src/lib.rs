@@ -0,0 +1,4 @@
+//! The parts of the game that run without bevy: the spatial grid the hit
+//! systems look up what a laser may hit in.
+
+pub mod grid;
@@ -1,28 +1,70 @@
+#![allow(clippy::type_complexity)]
+
+mod achievements;
+mod archetype;
+mod boss;
+mod bunker;
+mod daily;
+mod damage;
 mod enemy;
+mod formation;
+mod game_over;
+mod hud;
 mod player;
+mod powerup;
+mod score;
+mod stats;
+mod waves;
+mod weapon;
 use std::collections::HashSet;
 
+use archetype::ArchetypePlugin;
 use bevy::{prelude::*, sprite::collide_aabb::collide};
-use enemy::EnemyPlugin;
+use boss::BossPlugin;
+use bunker::{Bunker, BunkerPlugin};
+use daily::DailyPlugin;
+use damage::{DamagePlugin, HitEvent};
+use enemy::{Diving, EnemyPlugin};
+use game_over::GameOverPlugin;
+use hud::HudPlugin;
 use player::PlayerPlugin;
+use powerup::PowerUpPlugin;
+use rand::{rngs::StdRng, SeedableRng};
+use rust_common::{
+    achievements::AchievementsPlugin,
+    audio::{PlaySound, SoundPlugin},
+    daily::DailyChallenge,
+    gamepad::GamepadPlugin,
+    input::{Action, Binding, InputMapPlugin},
+    menu::not_paused,
+    particles::ParticlesPlugin,
+};
+use rust_invaders::grid::{Aabb, SpatialGrid};
+use score::ScorePlugin;
+use stats::StatsPlugin;
+use waves::WavesPlugin;
+use weapon::WeaponPlugin;
 
 const PLAYER_SPRITE: &str = "player.png";
 const PLAYER_LASER_SPRITE: &str = "laser_a_01.png";
 const ENEMY_SPRITE: &str = "enemy.png";
 const ENEMY_LASER_SPRITE: &str = "laser_b_01.png";
 const EXPLOSION_SHEET: &str = "explo_a_sheet.png";
-const TIME_STEPS: f32 = 1.0 / 60.0;
+const FONT: &str = "fonts/DejaVuSans.ttf";
 const WINDOW_WIDTH: f32 = 600.0;
 const WINDOW_HEIGHT: f32 = 600.0;
 const SCALE: f32 = 0.5;
-const MAX_ENEMIES: u32 = 2;
-const MAX_FORMATION_MEMBERS: u32 = 2;
+/// Side of the cells of the collision grid, about the size of an enemy.
+const COLLISION_CELL: f32 = 64.0;
 const PLAYER_RESPAWN_DELAY: f64 = 2.0;
+const START_LIVES: u32 = 3;
+const MAX_CONTINUES: u32 = 2;
+/// Kind of the enemies waves spawn when they name none.
+const BASIC_ENEMY: &str = "basic";
 // region: Resources
 struct Materials {
     player_materials: Handle<ColorMaterial>,
     player_laser_materials: Handle<ColorMaterial>,
-    enemy_materials: Handle<ColorMaterial>,
     enemy_laser_materials: Handle<ColorMaterial>,
     explosion_atlas: Handle<TextureAtlas>,
 }
@@ -34,49 +76,205 @@ struct WinSize {
 }
 
 struct ActiveEnemies(u32);
+
+/// Seconds the game advances this frame. Movement is scaled by it, so the
+/// game plays at the same speed at any frame rate.
+#[derive(Default)]
+struct TimeStep(f32);
+
+/// Enemies, enemy lasers and bunkers by where they are, filed anew every
+/// frame before the hit systems look up what a laser may hit.
+struct CollisionGrid {
+    enemies: SpatialGrid<Entity>,
+    enemy_lasers: SpatialGrid<Entity>,
+    bunkers: SpatialGrid<Entity>,
+}
+impl Default for CollisionGrid {
+    fn default() -> Self {
+        Self {
+            enemies: SpatialGrid::new(COLLISION_CELL, WINDOW_WIDTH, WINDOW_HEIGHT),
+            enemy_lasers: SpatialGrid::new(COLLISION_CELL, WINDOW_WIDTH, WINDOW_HEIGHT),
+            bunkers: SpatialGrid::new(COLLISION_CELL, WINDOW_WIDTH, WINDOW_HEIGHT),
+        }
+    }
+}
+
+/// Tuning on top of every wave that varies between runs, e.g. with the
+/// daily challenge.
+struct Difficulty {
+    /// Added to the enemies a wave has on screen at once.
+    extra_active: u32,
+    /// Multiplies the enemy speed of a wave.
+    speed_scale: f32,
+}
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            extra_active: 0,
+            speed_scale: 1.0,
+        }
+    }
+}
+
+/// Source of all gameplay randomness, so a run can be replayed from its seed.
+struct GameRng {
+    rng: StdRng,
+    seed: u64,
+}
+impl GameRng {
+    fn from_seed(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+        }
+    }
+}
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::from_seed(rand::random())
+    }
+}
+/// Lives and continues a game starts with.
+struct LifeRules {
+    lives: u32,
+    continues: u32,
+}
+impl Default for LifeRules {
+    fn default() -> Self {
+        Self {
+            lives: START_LIVES,
+            continues: MAX_CONTINUES,
+        }
+    }
+}
 struct PlayerState {
     on: bool,
     last_shot: f64,
+    lives: u32,
+    continues: u32,
+    /// Upgrade level of the weapon, one is lost with every life.
+    weapon_level: u32,
 }
-impl Default for PlayerState {
-    fn default() -> Self {
+impl PlayerState {
+    fn new_game(rules: &LifeRules) -> Self {
         Self {
             on: false,
             last_shot: 0.0,
+            lives: rules.lives,
+            continues: rules.continues,
+            weapon_level: 0,
         }
     }
-}
-impl PlayerState {
     fn shot(&mut self, time: f64) {
         self.on = false;
-        self.last_shot = time
+        self.last_shot = time;
+        self.lives = self.lives.saturating_sub(1);
+        self.weapon_level = self.weapon_level.saturating_sub(1);
     }
     fn spawned(&mut self) {
         self.on = true;
         self.last_shot = 0.0
     }
 }
+/// Where the game stands between playing and the results screen.
+enum GamePhase {
+    Playing,
+    /// Out of lives, the player can continue until the timer runs out.
+    Continue(Timer),
+    GameOver,
+}
+impl GamePhase {
+    fn playing(&self) -> bool {
+        matches!(self, Self::Playing)
+    }
+}
 // endregiom: Resources
 
 // region: Components
 struct Laser;
 struct Player;
-struct PlayerReadyFire(bool);
 struct FromPlayer;
-struct Enemy;
+/// Hit points a laser takes off what it hits.
+struct Damage(u32);
+/// The laser flies through enemies, hitting each of them once.
+struct Pierce(HashSet<Entity>);
+/// Lasers pass through the player until the timer runs out, and through
+/// entering bosses.
+struct Invulnerable(Timer);
+struct Enemy {
+    kind: String,
+}
+/// Hits left before destruction.
+struct Health(u32);
 struct FromEnemy;
 struct Explosion;
 struct ExplosionToSpawn(Vec3);
 struct Speed(f32);
+/// Pixels per second.
+struct Velocity(Vec2);
 // endregion: Components
+
+// region: Events
+/// An enemy of the given kind was destroyed by the player.
+struct EnemyKilled {
+    kind: String,
+    position: Vec3,
+}
+struct PlayerKilled;
+/// The player is out of lives and continues.
+struct GameEnded;
+// endregion: Events
 impl Default for Speed {
     fn default() -> Self {
         Self(500.0)
     }
 }
+fn default_bindings() -> Vec<(Action, Vec<Binding>)> {
+    use Binding::{Button, Key};
+    vec![
+        (Action::MoveLeft, vec![Key(KeyCode::Left)]),
+        (Action::MoveRight, vec![Key(KeyCode::Right)]),
+        // only free-roaming ships move up and down
+        (Action::MoveUp, vec![Key(KeyCode::Up)]),
+        (Action::MoveDown, vec![Key(KeyCode::Down)]),
+        (
+            Action::Fire,
+            vec![
+                Key(KeyCode::Space),
+                Button(GamepadButtonType::South),
+                Button(GamepadButtonType::East),
+            ],
+        ),
+        (
+            Action::Focus,
+            vec![Key(KeyCode::LShift), Button(GamepadButtonType::LeftTrigger)],
+        ),
+        (
+            Action::Pause,
+            vec![Key(KeyCode::Escape), Button(GamepadButtonType::Start)],
+        ),
+    ]
+}
+
 fn main() {
-    App::build()
-        .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("stats") {
+        if let Err(e) = rust_common::stats::run_command("invaders", &args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let daily = match DailyChallenge::from_args("invaders") {
+        Ok(daily) => daily,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut app = App::build();
+    app.insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
         .insert_resource(WindowDescriptor {
             title: "Rust Invaders".to_string(),
             width: WINDOW_WIDTH,
@@ -84,15 +282,81 @@ fn main() {
             ..Default::default()
         })
         .insert_resource(ActiveEnemies(0))
-        .add_plugins(DefaultPlugins)
+        .insert_resource(LifeRules::default())
+        .insert_resource(Difficulty::default())
+        .insert_resource(GameRng::default())
+        .insert_resource(CollisionGrid::default())
+        .insert_resource(TimeStep::default())
+        .add_plugins_with(DefaultPlugins, |group| {
+            // replaced by the SoundPlugin, which copes without an audio device
+            group.disable::<bevy::audio::AudioPlugin>()
+        })
         .add_startup_system(setup.system())
+        .add_system_to_stage(CoreStage::PreUpdate, advance_time_step.system())
+        .add_plugin(GamepadPlugin)
+        .add_plugin(InputMapPlugin {
+            game: "invaders",
+            defaults: default_bindings(),
+            font: FONT,
+        })
+        .add_plugin(SoundPlugin {
+            game: "invaders",
+            sounds: &["laser", "explosion", "player_hit"],
+            font: FONT,
+        })
+        .add_plugin(ParticlesPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(WeaponPlugin)
+        .add_plugin(ArchetypePlugin)
+        .add_plugin(WavesPlugin)
         .add_plugin(EnemyPlugin)
-        .add_system(laser_hit_enemy.system())
-        .add_system(laser_hit_player.system())
-        .add_system(explosion_to_spawn.system())
-        .add_system(animate_explosion.system())
-        .run();
+        .add_plugin(BossPlugin)
+        .add_plugin(BunkerPlugin)
+        .add_plugin(DamagePlugin)
+        .add_plugin(PowerUpPlugin)
+        .add_plugin(ScorePlugin)
+        .add_plugin(GameOverPlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(StatsPlugin)
+        .add_plugin(AchievementsPlugin {
+            game: "invaders",
+            font: FONT,
+        })
+        .add_event::<EnemyKilled>()
+        .add_event::<PlayerKilled>()
+        .add_event::<GameEnded>()
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(achievements::report_achievements.system())
+                .with_system(
+                    fill_collision_grid
+                        .system()
+                        .label("collision_grid")
+                        .after("movement"),
+                )
+                .with_system(
+                    laser_hit_enemy
+                        .system()
+                        .label("collision")
+                        .after("collision_grid"),
+                )
+                .with_system(
+                    laser_hit_player
+                        .system()
+                        .label("collision")
+                        .after("collision_grid"),
+                )
+                .with_system(enemy_hit_player.system().label("collision"))
+                .with_system(explosion_to_spawn.system())
+                .with_system(animate_explosion.system()),
+        );
+
+    if let Some(challenge) = daily {
+        app.add_plugin(DailyPlugin(challenge));
+    }
+
+    app.run();
 }
 
 fn setup(
@@ -106,6 +370,7 @@ fn setup(
 
     // camera
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(UiCameraBundle::default());
 
     // create main resources
     let texture_handle = assert_server.load(EXPLOSION_SHEET);
@@ -113,7 +378,6 @@ fn setup(
     commands.insert_resource(Materials {
         player_materials: materials.add(assert_server.load(PLAYER_SPRITE).into()),
         player_laser_materials: materials.add(assert_server.load(PLAYER_LASER_SPRITE).into()),
-        enemy_materials: materials.add(assert_server.load(ENEMY_SPRITE).into()),
         enemy_laser_materials: materials.add(assert_server.load(ENEMY_LASER_SPRITE).into()),
         explosion_atlas: texture_atlases.add(texture_atlas),
     });
@@ -123,74 +387,111 @@ fn setup(
     })
 }
 
-fn laser_hit_enemy(
-    mut commands: Commands,
-    mut laser_query: Query<(Entity, &Transform, &Sprite), (With<Laser>, With<FromPlayer>)>,
-    mut enemy_query: Query<(Entity, &Transform, &Sprite), With<Enemy>>,
-    mut active_enemies: ResMut<ActiveEnemies>,
-) {
-    let mut enemies_blasted: HashSet<Entity> = HashSet::new();
-
-    for (laser_entity, laser_tf, laser_sprite) in laser_query.iter_mut() {
-        for (enemy_entity, enemy_tf, enemy_sprite) in enemy_query.iter_mut() {
-            let laser_scale = Vec2::from(laser_tf.scale);
-            let enemy_scale = Vec2::from(enemy_tf.scale);
-            let collision = collide(
-                laser_tf.translation,
-                laser_sprite.size * laser_scale,
-                enemy_tf.translation,
-                enemy_sprite.size * enemy_scale,
-            );
+fn advance_time_step(time: Res<Time>, mut step: ResMut<TimeStep>) {
+    step.0 = time.delta_seconds();
+}
 
-            if let Some(_) = collision {
-                // Remove enemy
-                if enemies_blasted.get(&enemy_entity).is_none() {
-                    commands.entity(enemy_entity).despawn();
-                    active_enemies.0 -= 1;
+/// Box a sprite covers on screen.
+fn sprite_aabb(tf: &Transform, sprite: &Sprite) -> Aabb {
+    let size = sprite.size * Vec2::from(tf.scale);
+    Aabb::new((tf.translation.x, tf.translation.y), (size.x, size.y))
+}
 
-                    //Explosion
-                    commands
-                        .spawn()
-                        .insert(ExplosionToSpawn(enemy_tf.translation.clone()));
+fn fill_collision_grid(
+    mut grid: ResMut<CollisionGrid>,
+    enemy_query: Query<(Entity, &Transform, &Sprite), With<Enemy>>,
+    laser_query: Query<(Entity, &Transform, &Sprite), (With<Laser>, With<FromEnemy>)>,
+    bunker_query: Query<(Entity, &Transform, &Sprite), With<Bunker>>,
+) {
+    grid.enemies.clear();
+    for (entity, tf, sprite) in enemy_query.iter() {
+        grid.enemies.insert(entity, sprite_aabb(tf, sprite));
+    }
+    grid.enemy_lasers.clear();
+    for (entity, tf, sprite) in laser_query.iter() {
+        grid.enemy_lasers.insert(entity, sprite_aabb(tf, sprite));
+    }
+    grid.bunkers.clear();
+    for (entity, tf, sprite) in bunker_query.iter() {
+        grid.bunkers.insert(entity, sprite_aabb(tf, sprite));
+    }
+}
 
-                    enemies_blasted.insert(enemy_entity);
+fn laser_hit_enemy(
+    grid: Res<CollisionGrid>,
+    mut laser_query: Query<
+        (Entity, &Transform, &Sprite, &Damage, Option<&mut Pierce>),
+        (With<Laser>, With<FromPlayer>),
+    >,
+    mut hits: EventWriter<HitEvent>,
+) {
+    for (laser_entity, laser_tf, laser_sprite, damage, mut pierce) in laser_query.iter_mut() {
+        for (enemy_entity, _) in grid.enemies.query(sprite_aabb(laser_tf, laser_sprite)) {
+            // a piercing laser damages each enemy once on its way through
+            if let Some(pierce) = &pierce {
+                if pierce.0.contains(&enemy_entity) {
+                    continue;
                 }
-
-                // Remove laser
-                commands.entity(laser_entity).despawn();
+            }
+            hits.send(HitEvent {
+                attacker: laser_entity,
+                target: enemy_entity,
+                damage: damage.0,
+            });
+            match &mut pierce {
+                Some(pierce) => {
+                    pierce.0.insert(enemy_entity);
+                }
+                // a laser damages a single enemy
+                None => break,
             }
         }
     }
 }
 
 fn laser_hit_player(
-    mut commands: Commands,
-    mut player_state: ResMut<PlayerState>,
-    time: Res<Time>,
-    laser_query: Query<(Entity, &Transform, &Sprite), (With<Laser>, With<FromEnemy>)>,
+    grid: Res<CollisionGrid>,
     player_query: Query<(Entity, &Transform, &Sprite), With<Player>>,
+    mut hits: EventWriter<HitEvent>,
+) {
+    if let Ok((player_entity, player_tf, player_sprite)) = player_query.single() {
+        let player_box = sprite_aabb(player_tf, player_sprite);
+
+        for (laser_entity, _) in grid.enemy_lasers.query(player_box) {
+            hits.send(HitEvent {
+                attacker: laser_entity,
+                target: player_entity,
+                damage: 1,
+            });
+        }
+    }
+}
+
+/// Diving enemies crash into the player, destroying both.
+fn enemy_hit_player(
+    enemy_query: Query<(Entity, &Transform, &Sprite), (With<Enemy>, With<Diving>)>,
+    player_query: Query<(Entity, &Transform, &Sprite), With<Player>>,
+    mut hits: EventWriter<HitEvent>,
 ) {
     if let Ok((player_entity, player_tf, player_sprite)) = player_query.single() {
         let player_size = player_sprite.size * Vec2::from(player_tf.scale.abs());
 
-        for (laser_entity, laser_tf, laser_sprite) in laser_query.iter() {
-            let laser_size = laser_sprite.size * Vec2::from(laser_tf.scale.abs());
+        for (enemy_entity, enemy_tf, enemy_sprite) in enemy_query.iter() {
+            let enemy_size = enemy_sprite.size * Vec2::from(enemy_tf.scale.abs());
 
             let collision = collide(
-                laser_tf.translation,
-                laser_size,
+                enemy_tf.translation,
+                enemy_size,
                 player_tf.translation,
                 player_size,
             );
 
-            if let Some(_) = collision {
-                commands.entity(player_entity).despawn();
-                player_state.shot(time.seconds_since_startup());
-                commands.entity(laser_entity).despawn();
-
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(player_tf.translation.clone()));
+            if collision.is_some() {
+                hits.send(HitEvent {
+                    attacker: enemy_entity,
+                    target: player_entity,
+                    damage: 1,
+                });
             }
         }
     }
@@ -200,8 +501,10 @@ fn explosion_to_spawn(
     mut commands: Commands,
     query: Query<(Entity, &ExplosionToSpawn)>,
     materials: Res<Materials>,
+    mut sounds: EventWriter<PlaySound>,
 ) {
     for (explosion_spawn_entity, explosion_to_spawn) in query.iter() {
+        sounds.send(PlaySound("explosion"));
         commands
             .spawn_bundle(SpriteSheetBundle {
                 texture_atlas: materials.explosion_atlas.clone(),
@@ -241,3 +544,161 @@ fn animate_explosion(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::app::Events;
+    use damage::{BunkerHit, DeathEvent, DespawnEvent};
+    use formation::{FormationBook, FormationMaker};
+    use player::ShipControls;
+    use powerup::PowerUps;
+    use rust_common::{
+        input::{Action, ActionState},
+        particles::SpawnParticles,
+    };
+
+    /// Where the ship holding right, a player laser, an enemy laser and an
+    /// enemy flying its formation are after one simulated second at `rate`
+    /// updates per second.
+    fn positions_after_one_second(rate: u32) -> Vec<Vec3> {
+        let mut world = World::default();
+        let win_size = WinSize {
+            width: WINDOW_WIDTH,
+            height: WINDOW_HEIGHT,
+        };
+        let mut formation = FormationMaker::default().make(
+            &FormationBook::default(),
+            &[],
+            &win_size,
+            1,
+            &mut StdRng::seed_from_u64(7),
+        );
+        formation.progress = 100.0;
+        let joined = formation.point(formation.progress, &win_size).extend(10.0);
+        world.insert_resource(win_size);
+        world.insert_resource(TimeStep(1.0 / rate as f32));
+        world.insert_resource(Events::<DespawnEvent>::default());
+        let mut actions = ActionState::default();
+        actions.set(Action::MoveRight, 1.0);
+        world.insert_resource(actions);
+        world.insert_resource(ShipControls::default());
+
+        let entities = vec![
+            world
+                .spawn()
+                .insert_bundle((Player, Speed(100.0)))
+                .insert(Sprite::new(Vec2::new(30.0, 20.0)))
+                .insert(Transform::from_xyz(0.0, -250.0, 0.0))
+                .id(),
+            world
+                .spawn()
+                .insert_bundle((Laser, FromPlayer, Velocity(Vec2::new(40.0, 300.0))))
+                .insert(Transform::from_xyz(0.0, -200.0, 0.0))
+                .id(),
+            world
+                .spawn()
+                .insert_bundle((Laser, FromEnemy, Velocity(Vec2::new(-60.0, -240.0))))
+                .insert(Transform::from_xyz(50.0, 200.0, 0.0))
+                .id(),
+            world
+                .spawn()
+                .insert(Enemy {
+                    kind: BASIC_ENEMY.to_string(),
+                })
+                .insert(Speed(150.0))
+                .insert(formation)
+                .insert(Transform::from_translation(joined))
+                .id(),
+        ];
+
+        let mut stage = SystemStage::single_threaded();
+        stage
+            .add_system(player::player_movement.system())
+            .add_system(player::laser_movement.system())
+            .add_system(enemy::enemy_laser_movement.system())
+            .add_system(enemy::enemy_movement.system());
+        for _ in 0..rate {
+            stage.run(&mut world);
+        }
+
+        entities
+            .into_iter()
+            .map(|entity| world.get::<Transform>(entity).unwrap().translation)
+            .collect()
+    }
+
+    #[test]
+    fn movement_is_frame_rate_independent() {
+        let at_60 = positions_after_one_second(60);
+        for &rate in &[30, 144] {
+            for (position, expected) in positions_after_one_second(rate).iter().zip(&at_60) {
+                assert!(
+                    position.distance(*expected) < 0.1,
+                    "{:?} at {} Hz, {:?} at 60 Hz",
+                    position,
+                    rate,
+                    expected
+                );
+            }
+        }
+        // and they did move, the ship short of the window side
+        assert!((at_60[0].x - 100.0).abs() < 0.1);
+        assert!(at_60[1].distance(Vec3::new(0.0, -200.0, 0.0)) > 200.0);
+    }
+
+    #[test]
+    fn piercing_laser_damages_each_enemy_once() {
+        let mut world = World::default();
+        world.insert_resource(CollisionGrid::default());
+        world.insert_resource(PowerUps::default());
+        world.insert_resource(Events::<HitEvent>::default());
+        world.insert_resource(Events::<DeathEvent>::default());
+        world.insert_resource(Events::<DespawnEvent>::default());
+        world.insert_resource(Events::<BunkerHit>::default());
+        world.insert_resource(Events::<SpawnParticles>::default());
+
+        let enemies: Vec<Entity> = [-10.0, 10.0]
+            .iter()
+            .map(|&x| {
+                world
+                    .spawn()
+                    .insert(Enemy {
+                        kind: BASIC_ENEMY.to_string(),
+                    })
+                    .insert(Health(3))
+                    .insert(Sprite::new(Vec2::new(20.0, 20.0)))
+                    .insert(Transform::from_xyz(x, 0.0, 0.0))
+                    .id()
+            })
+            .collect();
+        // stays over both enemies for a few frames
+        let laser = world
+            .spawn()
+            .insert_bundle((Laser, FromPlayer, Damage(1), Pierce(HashSet::new())))
+            .insert(Sprite::new(Vec2::new(40.0, 10.0)))
+            .insert(Transform::default())
+            .id();
+
+        let mut stage = SystemStage::single_threaded();
+        stage
+            .add_system(fill_collision_grid.system().label("collision_grid"))
+            .add_system(
+                laser_hit_enemy
+                    .system()
+                    .label("collision")
+                    .after("collision_grid"),
+            )
+            .add_system(damage::apply_hits.system().after("collision"));
+        for _ in 0..5 {
+            stage.run(&mut world);
+        }
+
+        for enemy in enemies {
+            assert_eq!(world.get::<Health>(enemy).unwrap().0, 2);
+        }
+        assert_eq!(world.get::<Pierce>(laser).unwrap().0.len(), 2);
+        let hits = world.get_resource::<Events<HitEvent>>().unwrap();
+        assert_eq!(hits.get_reader().iter(hits).count(), 2);
+    }
+}
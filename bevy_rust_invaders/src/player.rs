@@ -1,25 +1,108 @@
+use std::{collections::HashSet, fs};
+
 use bevy::{core::FixedTimestep, prelude::*};
+use rust_common::{
+    asset_path,
+    audio::PlaySound,
+    input::{Action, ActionState},
+    menu::{not_paused, unless_paused},
+    particles::ParticleEmitter,
+};
+use serde::Deserialize;
 
 use crate::{
-    FromPlayer, Laser, Materials, Player, PlayerReadyFire, PlayerState, Speed, WinSize,
-    PLAYER_RESPAWN_DELAY, SCALE, TIME_STEPS,
+    damage::DespawnEvent,
+    powerup::{PowerUpKind, PowerUps},
+    waves::check_positive,
+    weapon::{Weapon, WeaponBook},
+    Damage, FromPlayer, GamePhase, Invulnerable, Laser, LifeRules, Materials, Pierce, Player,
+    PlayerState, Speed, TimeStep, Velocity, WinSize, PLAYER_RESPAWN_DELAY, SCALE,
 };
 
+const CONTROLS_FILE: &str = "controls.ron";
+const INVULNERABLE_SECONDS: f32 = 2.0;
+const BLINK_SECONDS: f32 = 0.1;
+
+// region: Resources
+/// How the ship moves, loaded from `assets/controls.ron`.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ShipControls {
+    /// The ship also moves up and down, within the lower third of the
+    /// window. Also turned on with `--free-roam`.
+    free_roam: bool,
+    /// Pixels kept between the ship and the window edges.
+    margin: f32,
+    /// Multiplies the speed while Focus is held.
+    focus_speed: f32,
+}
+
+impl Default for ShipControls {
+    fn default() -> Self {
+        Self {
+            free_roam: false,
+            margin: 5.0,
+            focus_speed: 0.4,
+        }
+    }
+}
+
+impl ShipControls {
+    /// The controls file, with `--free-roam` on the command line turning
+    /// free roaming on whatever it says.
+    fn load() -> Self {
+        let mut controls = fs::read_to_string(asset_path(CONTROLS_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str::<Self>(&content).map_err(|e| e.to_string()))
+            .and_then(|controls| controls.check().map(|_| controls))
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", CONTROLS_FILE, e);
+                Self::default()
+            });
+        controls.free_roam |= std::env::args().any(|arg| arg == "--free-roam");
+        controls
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if !(self.margin >= 0.0 && self.margin.is_finite()) {
+            return Err(format!("margin can't be below zero, not {}", self.margin));
+        }
+        check_positive("focus_speed", self.focus_speed)
+    }
+
+    /// Lowest and highest position of the center of a ship of `size`. It
+    /// only leaves the bottom when free-roaming.
+    fn bounds(&self, win_size: &WinSize, size: Vec2) -> (Vec2, Vec2) {
+        let window = Vec2::new(win_size.width, win_size.height);
+        let reach = (window / 2.0 - size.abs() / 2.0 - Vec2::splat(self.margin)).max(Vec2::ZERO);
+        // the ship stays within the lower third
+        let top = (-window.y / 6.0 - size.y.abs() / 2.0).max(-reach.y);
+        (-reach, Vec2::new(reach.x, top))
+    }
+}
+// endregion: Resources
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(PlayerState::default())
+        app.insert_resource(PlayerState::new_game(&LifeRules::default()))
+            .insert_resource(ShipControls::load())
             .add_startup_stage(
                 "game_setup_actors",
                 SystemStage::single(player_spawn.system()),
             )
-            .add_system(player_movement.system())
-            .add_system(player_shoots.system())
-            .add_system(laser_movement.system())
             .add_system_set(
                 SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(0.5))
+                    .with_run_criteria(not_paused.system())
+                    .with_system(player_movement.system())
+                    .with_system(player_shoots.system())
+                    .with_system(laser_movement.system().label("movement"))
+                    .with_system(player_invulnerability.system()),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(0.5).chain(unless_paused.system()))
                     .with_system(player_spawn.system()),
             );
     }
@@ -30,13 +113,19 @@ fn player_spawn(
     materials: Res<Materials>,
     window: Res<WinSize>,
     time: Res<Time>,
+    phase: Res<GamePhase>,
+    weapons: Res<WeaponBook>,
     mut player_state: ResMut<PlayerState>,
 ) {
     let now = time.seconds_since_startup();
     let last_shot = player_state.last_shot;
 
     // spawn sprite
-    if !player_state.on && (last_shot == 0.0 || now > last_shot + PLAYER_RESPAWN_DELAY) {
+    if phase.playing()
+        && player_state.lives > 0
+        && !player_state.on
+        && (last_shot == 0.0 || now > last_shot + PLAYER_RESPAWN_DELAY)
+    {
         let bottom = -window.height / 2.0;
 
         commands
@@ -50,80 +139,127 @@ fn player_spawn(
                 ..Default::default()
             })
             .insert(Player)
-            .insert(PlayerReadyFire(true))
-            .insert(Speed::default());
+            .insert(Weapon::new(&weapons, player_state.weapon_level))
+            .insert(Speed::default())
+            .insert(ParticleEmitter::new(
+                "engine_trail",
+                Vec3::new(0.0, -20.0, -1.0),
+            ))
+            // lasers already on screen can't hit right after spawning
+            .insert(Invulnerable(Timer::from_seconds(
+                INVULNERABLE_SECONDS,
+                false,
+            )));
         player_state.spawned();
     }
 }
 
-fn player_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Speed, &mut Transform, With<Player>)>,
+pub fn player_movement(
+    step: Res<TimeStep>,
+    actions: Res<ActionState>,
+    controls: Res<ShipControls>,
+    win_size: Res<WinSize>,
+    mut query: Query<(&Speed, &Sprite, &mut Transform), With<Player>>,
 ) {
-    if let Ok((speed, mut transform, _)) = query.single_mut() {
-        let dir = if keyboard_input.pressed(KeyCode::Left) {
-            -1.0
-        } else if keyboard_input.pressed(KeyCode::Right) {
-            1.0
+    if let Ok((speed, sprite, mut transform)) = query.single_mut() {
+        // the stick steers proportionally
+        let mut dir = Vec2::new(
+            actions.value(Action::MoveRight) - actions.value(Action::MoveLeft),
+            actions.value(Action::MoveUp) - actions.value(Action::MoveDown),
+        );
+        if !controls.free_roam {
+            dir.y = 0.0;
+        }
+        if dir.length() > 1.0 {
+            dir = dir.normalize();
+        }
+        let speed = if actions.pressed(Action::Focus) {
+            speed.0 * controls.focus_speed
         } else {
-            0.0
+            speed.0
         };
 
-        transform.translation.x += dir * speed.0 * TIME_STEPS;
+        let size = sprite.size * Vec2::from(transform.scale);
+        let (min, max) = controls.bounds(&win_size, size);
+        let position = (Vec2::from(transform.translation) + dir * speed * step.0).clamp(min, max);
+        transform.translation.x = position.x;
+        if controls.free_roam {
+            transform.translation.y = position.y;
+        }
     }
 }
 
 fn player_shoots(
     mut commands: Commands,
     materials: Res<Materials>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Transform, &mut PlayerReadyFire, With<Player>)>,
+    actions: Res<ActionState>,
+    power_ups: Res<PowerUps>,
+    mut sounds: EventWriter<PlaySound>,
+    mut query: Query<(&Transform, &mut Weapon), With<Player>>,
 ) {
-    if let Ok((player_tf, mut ready_fire, _)) = query.single_mut() {
-        if ready_fire.0 && keyboard_input.pressed(KeyCode::Space) {
+    if let Ok((player_tf, mut weapon)) = query.single_mut() {
+        // holding Fire keeps shooting as fast as the weapon allows
+        if actions.pressed(Action::Fire) && weapon.ready() {
             let pos_x = player_tf.translation.x;
             let pos_y = player_tf.translation.y;
+            let pierce = power_ups.has(PowerUpKind::Pierce);
 
-            let mut spawn_larsers = |x_offset: f32| {
-                commands
-                    .spawn_bundle(SpriteBundle {
-                        material: materials.player_laser_materials.clone(),
-                        transform: Transform {
-                            translation: Vec3::new(pos_x + x_offset, pos_y + 15., 0.0),
-                            scale: Vec3::new(SCALE, SCALE, 1.),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    })
+            for (x_offset, angle) in weapon.volley(&power_ups) {
+                let angle = angle.to_radians();
+                let velocity = Vec2::new(angle.sin(), angle.cos()) * weapon.stats.speed;
+                let mut laser = commands.spawn_bundle(SpriteBundle {
+                    material: materials.player_laser_materials.clone(),
+                    transform: Transform {
+                        translation: Vec3::new(pos_x + x_offset, pos_y + 15., 0.0),
+                        rotation: Quat::from_rotation_z(-angle),
+                        scale: Vec3::new(SCALE, SCALE, 1.),
+                    },
+                    ..Default::default()
+                });
+                laser
                     .insert(Laser)
                     .insert(FromPlayer)
-                    .insert(Speed::default());
-            };
-
-            let x_offset = 144.0 / 4. - 5.;
-            spawn_larsers(x_offset);
-            spawn_larsers(-x_offset);
-
-            ready_fire.0 = false;
-        }
+                    .insert(Velocity(velocity))
+                    .insert(Damage(weapon.stats.damage));
+                if pierce {
+                    laser.insert(Pierce(HashSet::new()));
+                }
+            }
+            sounds.send(PlaySound("laser"));
 
-        if keyboard_input.just_released(KeyCode::Space) {
-            ready_fire.0 = true;
+            weapon.fired(&power_ups);
         }
     }
 }
 
-fn laser_movement(
+pub fn laser_movement(
+    step: Res<TimeStep>,
     window: Res<WinSize>,
-    mut commands: Commands,
-    mut query: Query<(Entity, &Speed, &mut Transform), (With<Laser>, With<FromPlayer>)>,
+    mut despawns: EventWriter<DespawnEvent>,
+    mut query: Query<(Entity, &Velocity, &mut Transform), (With<Laser>, With<FromPlayer>)>,
 ) {
-    for (laser_entity, speed, mut laser_tf) in query.iter_mut() {
+    for (laser_entity, velocity, mut laser_tf) in query.iter_mut() {
         let translation = &mut laser_tf.translation;
-        translation.y += speed.0 * TIME_STEPS;
+        *translation += (velocity.0 * step.0).extend(0.0);
+
+        if translation.y > window.height || translation.x.abs() > window.width {
+            despawns.send(DespawnEvent(laser_entity));
+        }
+    }
+}
 
-        if translation.y > window.height {
-            commands.entity(laser_entity).despawn();
+fn player_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Invulnerable, &mut Visible), With<Player>>,
+) {
+    for (entity, mut invulnerable, mut visible) in query.iter_mut() {
+        if invulnerable.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+            visible.is_visible = true;
+        } else {
+            let blinks = (invulnerable.0.elapsed_secs() / BLINK_SECONDS) as u32;
+            visible.is_visible = blinks % 2 == 0;
         }
     }
 }
@@ -0,0 +1,356 @@
+use std::{collections::BTreeMap, mem};
+
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use rand::Rng;
+use rust_common::{audio::PlaySound, menu::not_paused};
+
+use crate::{
+    archetype::EnemyBook,
+    damage::{DespawnEvent, HitEvent},
+    weapon::WeaponBook,
+    Enemy, EnemyKilled, FromEnemy, GameRng, Invulnerable, Laser, Player, PlayerKilled, PlayerState,
+    TimeStep, WinSize, FONT,
+};
+
+const FALL_SPEED: f32 = 150.0;
+const PICKUP_SIZE: f32 = 22.0;
+const MAX_LIVES: u32 = 9;
+/// Damage a smart bomb does to every enemy on screen.
+const SMART_BOMB_DAMAGE: u32 = 5;
+/// Seconds the player can't be hit after the shield absorbed a hit.
+const SHIELD_GRACE_SECONDS: f32 = 1.0;
+
+// region: Definitions
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum PowerUpKind {
+    Spread,
+    Rapid,
+    Pierce,
+    Shield,
+    ExtraLife,
+    SmartBomb,
+    /// Raises the level of the weapon.
+    Upgrade,
+}
+
+/// What collecting a power-up that is still active does.
+enum Stacking {
+    /// Adds the duration, up to `max` seconds.
+    Extend { max: f32 },
+    /// Starts the duration over.
+    Refresh,
+    /// Adds a charge, up to `max`, and starts the duration over.
+    Charges { max: u32 },
+    /// Takes effect once, on collection.
+    Instant,
+}
+
+impl PowerUpKind {
+    /// Relative chances of the kinds when an enemy drops one.
+    const DROPS: [(Self, u32); 7] = [
+        (Self::Spread, 3),
+        (Self::Rapid, 3),
+        (Self::Pierce, 2),
+        (Self::Shield, 2),
+        (Self::ExtraLife, 1),
+        (Self::SmartBomb, 1),
+        (Self::Upgrade, 2),
+    ];
+
+    fn random(rng: &mut impl Rng) -> Self {
+        let total: u32 = Self::DROPS.iter().map(|(_, weight)| weight).sum();
+        let mut roll = rng.gen_range(0..total);
+        for (kind, weight) in Self::DROPS {
+            if roll < weight {
+                return kind;
+            }
+            roll -= weight;
+        }
+        Self::Spread
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Spread => "SPREAD",
+            Self::Rapid => "RAPID",
+            Self::Pierce => "PIERCE",
+            Self::Shield => "SHIELD",
+            Self::ExtraLife => "1UP",
+            Self::SmartBomb => "BOMB",
+            Self::Upgrade => "UPGRADE",
+        }
+    }
+
+    /// Shown on the falling pickup.
+    fn letter(self) -> &'static str {
+        match self {
+            Self::Spread => "W",
+            Self::Rapid => "R",
+            Self::Pierce => "P",
+            Self::Shield => "S",
+            Self::ExtraLife => "1",
+            Self::SmartBomb => "B",
+            Self::Upgrade => "U",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::Spread => Color::rgb(0.3, 0.6, 1.0),
+            Self::Rapid => Color::rgb(1.0, 0.8, 0.2),
+            Self::Pierce => Color::rgb(0.8, 0.3, 1.0),
+            Self::Shield => Color::rgb(0.3, 1.0, 0.8),
+            Self::ExtraLife => Color::rgb(0.3, 1.0, 0.3),
+            Self::SmartBomb => Color::rgb(1.0, 0.3, 0.3),
+            Self::Upgrade => Color::rgb(0.9, 0.9, 0.9),
+        }
+    }
+
+    /// Seconds the power-up lasts.
+    fn duration(self) -> f32 {
+        match self {
+            Self::Spread => 10.0,
+            Self::Rapid => 8.0,
+            Self::Pierce => 6.0,
+            Self::Shield => 20.0,
+            Self::ExtraLife | Self::SmartBomb | Self::Upgrade => 0.0,
+        }
+    }
+
+    fn stacking(self) -> Stacking {
+        match self {
+            Self::Spread => Stacking::Extend { max: 20.0 },
+            Self::Rapid => Stacking::Extend { max: 16.0 },
+            Self::Pierce => Stacking::Refresh,
+            Self::Shield => Stacking::Charges { max: 3 },
+            Self::ExtraLife | Self::SmartBomb | Self::Upgrade => Stacking::Instant,
+        }
+    }
+}
+// endregion: Definitions
+
+// region: Resources
+pub struct Active {
+    /// Seconds left.
+    pub remaining: f32,
+    pub charges: u32,
+}
+
+/// Power-ups the player has collected and still enjoys. They are all lost
+/// with a life.
+#[derive(Default)]
+pub struct PowerUps {
+    pub active: BTreeMap<PowerUpKind, Active>,
+}
+
+impl PowerUps {
+    pub fn has(&self, kind: PowerUpKind) -> bool {
+        self.active.contains_key(&kind)
+    }
+
+    fn collect(&mut self, kind: PowerUpKind) {
+        let duration = kind.duration();
+        let stacking = kind.stacking();
+        if matches!(stacking, Stacking::Instant) {
+            return;
+        }
+        let active = self.active.entry(kind).or_insert(Active {
+            remaining: 0.0,
+            charges: 0,
+        });
+        match stacking {
+            Stacking::Extend { max } => active.remaining = (active.remaining + duration).min(max),
+            Stacking::Refresh => active.remaining = duration,
+            Stacking::Charges { max } => {
+                active.remaining = duration;
+                active.charges = (active.charges + 1).min(max);
+            }
+            Stacking::Instant => {}
+        }
+    }
+
+    /// Uses up a shield charge to survive a hit, if there is one.
+    pub fn absorb_hit(&mut self) -> bool {
+        match self.active.get_mut(&PowerUpKind::Shield) {
+            Some(shield) => {
+                shield.charges -= 1;
+                if shield.charges == 0 {
+                    self.active.remove(&PowerUpKind::Shield);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+// endregion: Resources
+
+// region: Components
+pub struct PowerUp(PowerUpKind);
+// endregion: Components
+
+/// Destroyed enemies drop power-ups now and then, which fall towards the
+/// player and are collected by touching them.
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(PowerUps::default()).add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(drop_power_ups.system())
+                .with_system(power_up_movement.system())
+                .with_system(collect_power_ups.system())
+                .with_system(expire_power_ups.system()),
+        );
+    }
+}
+
+/// The grace period after the shield took a hit, when the player blinks as
+/// after spawning.
+pub fn shield_grace() -> Invulnerable {
+    Invulnerable(Timer::from_seconds(SHIELD_GRACE_SECONDS, false))
+}
+
+fn drop_power_ups(
+    mut commands: Commands,
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut rng: ResMut<GameRng>,
+    book: Res<EnemyBook>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for killed in enemy_killed.iter() {
+        if !rng
+            .rng
+            .gen_bool(book.get(&killed.kind).drop_chance.clamp(0.0, 1.0) as f64)
+        {
+            continue;
+        }
+        let kind = PowerUpKind::random(&mut rng.rng);
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(kind.color().into()),
+                sprite: Sprite::new(Vec2::splat(PICKUP_SIZE)),
+                transform: Transform::from_translation(killed.position.truncate().extend(5.0)),
+                ..Default::default()
+            })
+            .insert(PowerUp(kind))
+            .with_children(|parent| {
+                parent.spawn_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        kind.letter(),
+                        TextStyle {
+                            font: asset_server.load(FONT),
+                            font_size: 16.0,
+                            color: Color::BLACK,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+fn power_up_movement(
+    mut commands: Commands,
+    step: Res<TimeStep>,
+    win_size: Res<WinSize>,
+    mut query: Query<(Entity, &mut Transform), With<PowerUp>>,
+) {
+    for (entity, mut tf) in query.iter_mut() {
+        tf.translation.y -= FALL_SPEED * step.0;
+        if tf.translation.y < -win_size.height / 2.0 - PICKUP_SIZE {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn collect_power_ups(
+    mut commands: Commands,
+    mut power_ups: ResMut<PowerUps>,
+    mut player_state: ResMut<PlayerState>,
+    weapons: Res<WeaponBook>,
+    mut hits: EventWriter<HitEvent>,
+    mut despawns: EventWriter<DespawnEvent>,
+    mut sounds: EventWriter<PlaySound>,
+    player_query: Query<(Entity, &Transform, &Sprite), With<Player>>,
+    pickup_query: Query<(Entity, &Transform, &PowerUp)>,
+    enemy_query: Query<Entity, With<Enemy>>,
+    laser_query: Query<Entity, (With<Laser>, With<FromEnemy>)>,
+) {
+    let (player_entity, player_tf, player_sprite) = match player_query.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let player_size = player_sprite.size * Vec2::from(player_tf.scale.abs());
+
+    for (entity, tf, power_up) in pickup_query.iter() {
+        let collision = collide(
+            tf.translation,
+            Vec2::splat(PICKUP_SIZE),
+            player_tf.translation,
+            player_size,
+        );
+        if collision.is_none() {
+            continue;
+        }
+        commands.entity(entity).despawn_recursive();
+        sounds.send(PlaySound("laser"));
+        info!("power-up {}", power_up.0.name());
+
+        match power_up.0 {
+            PowerUpKind::ExtraLife => {
+                player_state.lives = (player_state.lives + 1).min(MAX_LIVES);
+            }
+            PowerUpKind::Upgrade => {
+                player_state.weapon_level =
+                    (player_state.weapon_level + 1).min(weapons.max_level());
+            }
+            PowerUpKind::SmartBomb => {
+                for laser in laser_query.iter() {
+                    despawns.send(DespawnEvent(laser));
+                }
+                // the player hits every enemy at once
+                for enemy in enemy_query.iter() {
+                    hits.send(HitEvent {
+                        attacker: player_entity,
+                        target: enemy,
+                        damage: SMART_BOMB_DAMAGE,
+                    });
+                }
+            }
+            kind => power_ups.collect(kind),
+        }
+    }
+}
+
+fn expire_power_ups(
+    time: Res<Time>,
+    mut power_ups: ResMut<PowerUps>,
+    mut player_killed: EventReader<PlayerKilled>,
+) {
+    if player_killed.iter().next().is_some() {
+        power_ups.active.clear();
+    }
+
+    let delta = time.delta_seconds();
+    power_ups.active = mem::take(&mut power_ups.active)
+        .into_iter()
+        .filter_map(|(kind, mut active)| {
+            active.remaining -= delta;
+            if active.remaining > 0.0 {
+                Some((kind, active))
+            } else {
+                None
+            }
+        })
+        .collect();
+}
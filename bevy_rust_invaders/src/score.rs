@@ -0,0 +1,118 @@
+use std::fs;
+
+use bevy::{app::AppExit, prelude::*};
+use rust_common::menu::not_paused;
+use serde::{Deserialize, Serialize};
+
+use crate::{archetype::EnemyBook, daily::DailyRun, EnemyKilled, PlayerKilled};
+
+const HI_SCORE_FILE: &str = "invaders_hiscore.ron";
+/// Seconds after a kill in which the next one raises the combo.
+const COMBO_WINDOW: f64 = 1.5;
+const MAX_COMBO: u32 = 8;
+
+// region: Resources
+#[derive(Default)]
+pub struct Score {
+    pub points: u32,
+    pub kills: u32,
+    /// Kills in quick succession, multiplying the points of the last one.
+    pub combo: u32,
+    last_kill: f64,
+}
+
+impl Score {
+    /// Whether another kill right now would raise the combo.
+    pub fn combo_running(&self, now: f64) -> bool {
+        self.combo > 1 && now - self.last_kill <= COMBO_WINDOW
+    }
+}
+
+/// Best score ever, saved to `invaders_hiscore.ron`.
+#[derive(Serialize, Deserialize, Default)]
+pub struct HiScore {
+    pub score: u32,
+}
+
+impl HiScore {
+    fn load() -> Self {
+        fs::read_to_string(HI_SCORE_FILE)
+            .ok()
+            .and_then(|content| ron::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(HI_SCORE_FILE, content).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            warn!("could not save hi-score: {}", e);
+        }
+    }
+
+    /// Takes over `score` if it beats the record and persists it.
+    pub fn submit(&mut self, score: u32) {
+        if score > self.score {
+            info!("new hi-score: {}", score);
+            self.score = score;
+            self.save();
+        }
+    }
+}
+// endregion: Resources
+
+/// Counts points. Daily runs are scored by their own leaderboard
+/// and don't touch the hi-score.
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Score::default())
+            .insert_resource(HiScore::load())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(score_kills.system())
+                    .with_system(break_combo.system()),
+            )
+            .add_system_to_stage(CoreStage::Last, save_hi_score.system());
+    }
+}
+
+fn score_kills(
+    time: Res<Time>,
+    book: Res<EnemyBook>,
+    mut score: ResMut<Score>,
+    mut enemy_killed: EventReader<EnemyKilled>,
+) {
+    let now = time.seconds_since_startup();
+    for EnemyKilled { kind, .. } in enemy_killed.iter() {
+        score.combo = if now - score.last_kill <= COMBO_WINDOW {
+            (score.combo + 1).min(MAX_COMBO)
+        } else {
+            1
+        };
+        score.last_kill = now;
+        score.kills += 1;
+        score.points += book.get(kind).points * score.combo;
+    }
+}
+
+fn break_combo(mut score: ResMut<Score>, mut player_killed: EventReader<PlayerKilled>) {
+    if player_killed.iter().next().is_some() {
+        score.combo = 0;
+    }
+}
+
+fn save_hi_score(
+    mut app_exit: EventReader<AppExit>,
+    score: Res<Score>,
+    mut hi_score: ResMut<HiScore>,
+    daily: Option<Res<DailyRun>>,
+) {
+    if app_exit.iter().next().is_some() && daily.is_none() {
+        hi_score.submit(score.points);
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use bevy::{app::AppExit, prelude::*};
+use rust_common::{
+    menu::not_paused,
+    stats::{unix_time, StatsLog},
+};
+use serde::Serialize;
+
+use crate::{
+    daily::DailyRun, damage::HitEvent, score::Score, waves::Wave, Enemy, EnemyKilled, FromPlayer,
+    GameEnded, GamePhase, GameRng, Invulnerable, Laser, PlayerKilled,
+};
+
+/// One line of `invaders_stats.jsonl`.
+#[derive(Serialize)]
+struct GameSummary {
+    finished_at: u64,
+    duration_secs: f64,
+    shots_fired: u32,
+    hits: u32,
+    accuracy: f32,
+    enemies_killed: BTreeMap<String, u32>,
+    lives_lost: u32,
+    wave_reached: u32,
+    score: u32,
+    seed: u64,
+    daily: bool,
+}
+
+// region: Resources
+#[derive(Default)]
+struct CurrentGame {
+    started_at: f64,
+    shots_fired: u32,
+    hits: u32,
+    enemies_killed: BTreeMap<String, u32>,
+    lives_lost: u32,
+}
+// endregion: Resources
+
+/// Records a summary per game, when it ends or when the app exits during it.
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(CurrentGame::default())
+            .insert_resource(StatsLog::new("invaders"))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(count_shots.system())
+                    // while the lasers that hit are still around
+                    .with_system(count_hits.system().after("collision"))
+                    .with_system(count_kills_and_deaths.system()),
+            )
+            // AppExit is sent during Update, the runner stops after this frame
+            .add_system_to_stage(CoreStage::Last, record_game.system());
+    }
+}
+
+fn count_shots(
+    mut game: ResMut<CurrentGame>,
+    query: Query<Entity, (With<Laser>, Added<FromPlayer>)>,
+) {
+    game.shots_fired += query.iter().count() as u32;
+}
+
+fn count_hits(
+    mut game: ResMut<CurrentGame>,
+    mut hits: EventReader<HitEvent>,
+    laser_query: Query<Entity, (With<Laser>, With<FromPlayer>)>,
+    enemy_query: Query<Entity, (With<Enemy>, Without<Invulnerable>)>,
+) {
+    game.hits += hits
+        .iter()
+        .filter(|hit| laser_query.get(hit.attacker).is_ok() && enemy_query.get(hit.target).is_ok())
+        .count() as u32;
+}
+
+fn count_kills_and_deaths(
+    mut game: ResMut<CurrentGame>,
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut player_killed: EventReader<PlayerKilled>,
+) {
+    for EnemyKilled { kind, .. } in enemy_killed.iter() {
+        *game.enemies_killed.entry(kind.clone()).or_default() += 1;
+    }
+    game.lives_lost += player_killed.iter().count() as u32;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_game(
+    mut app_exit: EventReader<AppExit>,
+    mut game_ended: EventReader<GameEnded>,
+    mut game: ResMut<CurrentGame>,
+    log: Res<StatsLog>,
+    time: Res<Time>,
+    rng: Res<GameRng>,
+    wave: Res<Wave>,
+    score: Res<Score>,
+    phase: Res<GamePhase>,
+    daily: Option<Res<DailyRun>>,
+) {
+    let ended = game_ended.iter().next().is_some();
+    // a game over was already recorded when it happened
+    let quit = app_exit.iter().next().is_some() && !matches!(*phase, GamePhase::GameOver);
+    if !(ended || quit) {
+        return;
+    }
+
+    let now = time.seconds_since_startup();
+    let game = std::mem::replace(
+        &mut *game,
+        CurrentGame {
+            started_at: now,
+            ..Default::default()
+        },
+    );
+    let summary = GameSummary {
+        finished_at: unix_time(),
+        duration_secs: now - game.started_at,
+        shots_fired: game.shots_fired,
+        hits: game.hits,
+        accuracy: if game.shots_fired == 0 {
+            0.0
+        } else {
+            game.hits as f32 / game.shots_fired as f32
+        },
+        enemies_killed: game.enemies_killed,
+        lives_lost: game.lives_lost,
+        wave_reached: wave.number,
+        score: score.points,
+        seed: rng.seed,
+        daily: daily.is_some(),
+    };
+    if let Err(e) = log.append(&summary) {
+        warn!("could not write game stats: {}", e);
+    }
+}
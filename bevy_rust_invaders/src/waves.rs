@@ -0,0 +1,342 @@
+use std::fs;
+
+use bevy::prelude::*;
+use rust_common::{asset_path, menu::not_paused};
+use serde::Deserialize;
+
+use crate::{
+    archetype::EnemyBook, boss::Boss, formation::FormationBook, ActiveEnemies, Difficulty,
+    GamePhase, BASIC_ENEMY, FONT,
+};
+
+const WAVES_FILE: &str = "waves.ron";
+const BREATHER_SECONDS: f32 = 3.0;
+/// Scaling never brings spawns or volleys closer together than this.
+const MIN_INTERVAL: f32 = 0.2;
+
+// region: Definitions
+/// Checks seconds or a factor of them that end up in a `Timer`, which
+/// panics on negative ones, or a distance something is divided by.
+pub fn check_positive(field: &str, value: f32) -> Result<(), String> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("{} has to be above zero, not {}", field, value))
+    }
+}
+
+/// One wave, loaded from `assets/waves.ron`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct WaveDef {
+    /// Enemies to destroy to clear the wave.
+    pub enemies: u32,
+    /// Enemies on screen at once.
+    pub max_active: u32,
+    /// Enemies flying the same formation.
+    pub group_size: u32,
+    /// Enemy kinds, spawned in turn.
+    pub kinds: Vec<String>,
+    /// Names of the formations of `assets/formations.ron` groups pick
+    /// from, all of them if empty.
+    #[serde(default)]
+    pub formations: Vec<String>,
+    /// Name of the boss of `assets/bosses.ron` the wave starts with.
+    #[serde(default)]
+    pub boss: Option<String>,
+    /// Bunkers put up at the start of the wave.
+    #[serde(default)]
+    pub bunkers: u32,
+    /// Seconds between two spawns.
+    pub spawn_interval: f32,
+    /// Average seconds between two volleys of an enemy.
+    pub fire_interval: f32,
+    pub enemy_speed: f32,
+}
+
+impl WaveDef {
+    fn check(&self) -> Result<(), String> {
+        // no enemy would ever come to be destroyed
+        if self.enemies > 0 && self.max_active == 0 {
+            return Err("max_active has to be above zero".to_string());
+        }
+        check_positive("spawn_interval", self.spawn_interval)?;
+        check_positive("fire_interval", self.fire_interval)
+    }
+}
+
+/// How every wave past the last defined one gets harder than the one before.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Scaling {
+    /// Added to `enemies`.
+    pub enemies: u32,
+    /// Added to `max_active`, up to `max_active_limit`.
+    pub max_active: u32,
+    pub max_active_limit: u32,
+    /// Multiplies `enemy_speed`.
+    pub speed: f32,
+    /// Multiplies `spawn_interval` and `fire_interval`.
+    pub interval: f32,
+    /// Every that many waves past the last defined one, the next of
+    /// `bosses` shows up instead of the boss of that wave.
+    #[serde(default)]
+    pub boss_every: u32,
+    #[serde(default)]
+    pub bosses: Vec<String>,
+}
+
+// Resource
+#[derive(Deserialize)]
+pub struct WaveBook {
+    waves: Vec<WaveDef>,
+    scaling: Scaling,
+}
+
+impl Default for WaveBook {
+    /// A single endless-feeling wave, as the game played before waves.
+    fn default() -> Self {
+        Self {
+            waves: vec![WaveDef {
+                enemies: 10,
+                max_active: 2,
+                group_size: 2,
+                kinds: vec![BASIC_ENEMY.to_string()],
+                formations: Vec::new(),
+                boss: None,
+                bunkers: 4,
+                spawn_interval: 1.0,
+                fire_interval: 0.9,
+                enemy_speed: 500.0,
+            }],
+            scaling: Scaling {
+                enemies: 2,
+                max_active: 1,
+                max_active_limit: 8,
+                speed: 1.05,
+                interval: 0.95,
+                boss_every: 0,
+                bosses: Vec::new(),
+            },
+        }
+    }
+}
+
+impl WaveBook {
+    fn load() -> Self {
+        fs::read_to_string(asset_path(WAVES_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str::<Self>(&content).map_err(|e| e.to_string()))
+            .and_then(|book| {
+                if book.waves.is_empty() {
+                    return Err("no waves defined".to_string());
+                }
+                for (i, wave) in book.waves.iter().enumerate() {
+                    wave.check().map_err(|e| format!("wave {}: {}", i + 1, e))?;
+                }
+                check_positive("scaling interval", book.scaling.interval)?;
+                if book.scaling.max_active_limit == 0 {
+                    return Err("scaling max_active_limit has to be above zero".to_string());
+                }
+                Ok(book)
+            })
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", WAVES_FILE, e);
+                Self::default()
+            })
+    }
+
+    /// Definition of wave `number`, counted from 1.
+    pub fn wave(&self, number: u32, difficulty: &Difficulty) -> WaveDef {
+        let index = (number.max(1) - 1) as usize;
+        let last = self.waves.len() - 1;
+        let mut def = self.waves[index.min(last)].clone();
+
+        let extra = index.saturating_sub(last) as u32;
+        let scaling = &self.scaling;
+        def.enemies += scaling.enemies * extra;
+        def.max_active = (def.max_active + scaling.max_active * extra)
+            .min(scaling.max_active_limit.max(def.max_active));
+        def.enemy_speed *= scaling.speed.powi(extra as i32);
+        def.spawn_interval = (def.spawn_interval * scaling.interval.powi(extra as i32))
+            .max(MIN_INTERVAL.min(def.spawn_interval));
+        def.fire_interval = (def.fire_interval * scaling.interval.powi(extra as i32))
+            .max(MIN_INTERVAL.min(def.fire_interval));
+        if extra > 0 {
+            def.boss = None;
+            if scaling.boss_every > 0
+                && extra % scaling.boss_every == 0
+                && !scaling.bosses.is_empty()
+            {
+                let next = (extra / scaling.boss_every - 1) as usize;
+                def.boss = Some(scaling.bosses[next % scaling.bosses.len()].clone());
+            }
+        }
+
+        def.max_active += difficulty.extra_active;
+        def.enemy_speed *= difficulty.speed_scale;
+        def.group_size = def.group_size.max(1);
+        if def.kinds.is_empty() {
+            def.kinds.push(BASIC_ENEMY.to_string());
+        }
+        def
+    }
+}
+// endregion: Definitions
+
+// region: Resources
+pub enum WaveStage {
+    /// The banner of the coming wave is up.
+    Breather(Timer),
+    Fighting {
+        def: WaveDef,
+        spawn_timer: Timer,
+    },
+}
+
+pub struct Wave {
+    pub number: u32,
+    /// Enemies of this wave spawned so far.
+    pub spawned: u32,
+    pub stage: WaveStage,
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            number: 1,
+            spawned: 0,
+            stage: WaveStage::Breather(Timer::from_seconds(BREATHER_SECONDS, false)),
+        }
+    }
+}
+// endregion: Resources
+
+// region: Components
+struct WaveBanner;
+// endregion: Components
+
+/// Plays the waves of `assets/waves.ron` one after the other. A wave is
+/// cleared once all its enemies are destroyed, then the banner of the next
+/// one stays up for a short breather.
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(WaveBook::load())
+            .insert_resource(Wave::default())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(advance_waves.system())
+                    .with_system(draw_banner.system()),
+            );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn advance_waves(
+    time: Res<Time>,
+    book: Res<WaveBook>,
+    formations: Res<FormationBook>,
+    enemies: Res<EnemyBook>,
+    difficulty: Res<Difficulty>,
+    active_enemies: Res<ActiveEnemies>,
+    phase: Res<GamePhase>,
+    mut wave: ResMut<Wave>,
+    bosses: Query<&Boss>,
+) {
+    if !phase.playing() {
+        return;
+    }
+
+    let wave = &mut *wave;
+    match &mut wave.stage {
+        WaveStage::Breather(timer) => {
+            if timer.tick(time.delta()).finished() {
+                let def = book.wave(wave.number, &difficulty);
+                match &def.boss {
+                    Some(boss) => {
+                        info!("wave {}: {} enemies and {}", wave.number, def.enemies, boss)
+                    }
+                    None => info!("wave {}: {} enemies", wave.number, def.enemies),
+                }
+                for name in def
+                    .formations
+                    .iter()
+                    .filter(|name| !formations.contains(name))
+                {
+                    warn!("wave {} flies unknown formation {}", wave.number, name);
+                }
+                for kind in def.kinds.iter().filter(|kind| !enemies.contains(kind)) {
+                    warn!("wave {} spawns unknown enemy {}", wave.number, kind);
+                }
+                wave.spawned = 0;
+                wave.stage = WaveStage::Fighting {
+                    spawn_timer: Timer::from_seconds(def.spawn_interval, true),
+                    def,
+                };
+            }
+        }
+        WaveStage::Fighting { def, .. } => {
+            let boss_due = def.boss.is_some() || bosses.iter().next().is_some();
+            if wave.spawned >= def.enemies && active_enemies.0 == 0 && !boss_due {
+                wave.number += 1;
+                wave.stage = WaveStage::Breather(Timer::from_seconds(BREATHER_SECONDS, false));
+            }
+        }
+    }
+}
+
+fn draw_banner(
+    mut commands: Commands,
+    mut shown: Local<Option<u32>>,
+    wave: Res<Wave>,
+    phase: Res<GamePhase>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    banners: Query<Entity, With<WaveBanner>>,
+) {
+    let banner = match wave.stage {
+        WaveStage::Breather(_) if phase.playing() => Some(wave.number),
+        _ => None,
+    };
+    if *shown == banner {
+        return;
+    }
+    *shown = banner;
+
+    for entity in banners.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let number = match banner {
+        Some(number) => number,
+        None => return,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(WaveBanner)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("WAVE {}", number),
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
@@ -0,0 +1,194 @@
+use std::fs;
+
+use bevy::prelude::*;
+use rust_common::{asset_path, menu::not_paused};
+use serde::Deserialize;
+
+use crate::{
+    powerup::{PowerUpKind, PowerUps},
+    Player, PlayerState,
+};
+
+const WEAPONS_FILE: &str = "weapons.ron";
+/// Heat lost per second.
+const COOLING: f32 = 0.35;
+/// An overheated weapon fires again once it cooled down to this.
+const RECOVERED_HEAT: f32 = 0.25;
+/// Half the distance between the outer lasers of a volley.
+const VOLLEY_HALF_WIDTH: f32 = 144.0 / 4. - 5.;
+/// Degrees the extra lasers of the spread power-up fly off straight up.
+const SPREAD_ANGLE: f32 = 15.0;
+/// Multiplies the fire rate while rapid fire is active.
+const RAPID_FIRE: f32 = 2.0;
+
+// region: Definitions
+/// One upgrade level of the player's weapon, loaded from
+/// `assets/weapons.ron`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct WeaponLevel {
+    /// Volleys per second while Fire is held.
+    pub fire_rate: f32,
+    /// Lasers per volley.
+    pub count: u32,
+    /// Degrees between the outer lasers of a volley.
+    pub spread: f32,
+    /// Pixels per second.
+    pub speed: f32,
+    /// Hit points a laser takes off an enemy.
+    pub damage: u32,
+    /// Heat a volley adds, the weapon overheats at 1. No heat if 0.
+    #[serde(default)]
+    pub heat_per_shot: f32,
+}
+
+// Resource
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct WeaponBook(Vec<WeaponLevel>);
+
+impl Default for WeaponBook {
+    /// The two parallel lasers the ship fired before it had weapons.
+    fn default() -> Self {
+        Self(vec![WeaponLevel {
+            fire_rate: 4.0,
+            count: 2,
+            spread: 0.0,
+            speed: 500.0,
+            damage: 1,
+            heat_per_shot: 0.0,
+        }])
+    }
+}
+
+impl WeaponBook {
+    fn load() -> Self {
+        fs::read_to_string(asset_path(WEAPONS_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str::<Self>(&content).map_err(|e| e.to_string()))
+            .and_then(|book| {
+                if book.0.is_empty() {
+                    Err("no weapon levels defined".to_string())
+                } else {
+                    Ok(book)
+                }
+            })
+            .unwrap_or_else(|e| {
+                warn!("could not load {}: {}", WEAPONS_FILE, e);
+                Self::default()
+            })
+    }
+
+    pub fn max_level(&self) -> u32 {
+        self.0.len() as u32 - 1
+    }
+
+    fn level(&self, level: u32) -> &WeaponLevel {
+        &self.0[(level as usize).min(self.0.len() - 1)]
+    }
+}
+// endregion: Definitions
+
+// region: Components
+pub struct Weapon {
+    /// Upgrade level, counted from 0.
+    pub level: u32,
+    pub stats: WeaponLevel,
+    /// Seconds until the next volley.
+    cooldown: f32,
+    /// From 0 to 1.
+    pub heat: f32,
+    pub overheated: bool,
+}
+
+impl Weapon {
+    pub fn new(book: &WeaponBook, level: u32) -> Self {
+        Self {
+            level,
+            stats: book.level(level).clone(),
+            cooldown: 0.0,
+            heat: 0.0,
+            overheated: false,
+        }
+    }
+
+    pub fn ready(&self) -> bool {
+        self.cooldown <= 0.0 && !self.overheated
+    }
+
+    /// Starts the cooldown and heats up after firing a volley.
+    pub fn fired(&mut self, power_ups: &PowerUps) {
+        let rate = if power_ups.has(PowerUpKind::Rapid) {
+            self.stats.fire_rate * RAPID_FIRE
+        } else {
+            self.stats.fire_rate
+        };
+        self.cooldown = 1.0 / rate.max(0.1);
+        self.heat += self.stats.heat_per_shot;
+        if self.heat >= 1.0 {
+            self.heat = 1.0;
+            self.overheated = true;
+        }
+    }
+
+    /// Lasers of one volley with the power-ups at hand, as x offset and
+    /// angle in degrees from straight up.
+    pub fn volley(&self, power_ups: &PowerUps) -> Vec<(f32, f32)> {
+        let count = self.stats.count.max(1);
+        let mut lasers: Vec<(f32, f32)> = (0..count)
+            .map(|i| {
+                // -1 for the leftmost laser to 1 for the rightmost
+                let side = if count > 1 {
+                    2.0 * i as f32 / (count - 1) as f32 - 1.0
+                } else {
+                    0.0
+                };
+                (side * VOLLEY_HALF_WIDTH, side * self.stats.spread / 2.0)
+            })
+            .collect();
+        if power_ups.has(PowerUpKind::Spread) {
+            lasers.push((0.0, -SPREAD_ANGLE));
+            lasers.push((0.0, SPREAD_ANGLE));
+        }
+        lasers
+    }
+}
+// endregion: Components
+
+/// The player's weapon: its upgrade level, cooldown and heat.
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(WeaponBook::load()).add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(cool_weapon.system())
+                .with_system(upgrade_weapon.system()),
+        );
+    }
+}
+
+fn cool_weapon(time: Res<Time>, mut query: Query<&mut Weapon, With<Player>>) {
+    let delta = time.delta_seconds();
+    for mut weapon in query.iter_mut() {
+        weapon.cooldown = (weapon.cooldown - delta).max(0.0);
+        weapon.heat = (weapon.heat - COOLING * delta).max(0.0);
+        if weapon.overheated && weapon.heat <= RECOVERED_HEAT {
+            weapon.overheated = false;
+        }
+    }
+}
+
+/// Follows the level the player reached with upgrades.
+fn upgrade_weapon(
+    book: Res<WeaponBook>,
+    player_state: Res<PlayerState>,
+    mut query: Query<&mut Weapon, With<Player>>,
+) {
+    for mut weapon in query.iter_mut() {
+        if weapon.level != player_state.weapon_level {
+            weapon.level = player_state.weapon_level;
+            weapon.stats = book.level(weapon.level).clone();
+        }
+    }
+}
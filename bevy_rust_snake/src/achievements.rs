@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use rust_common::achievements::AchievementEvent;
+
+use crate::{Arena, GameOverEvent, GrowthEvent, SnakeSegments};
+
+/// Translates snake events into achievement progress.
+pub fn report_achievements(
+    mut growth_reader: EventReader<GrowthEvent>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut achievements: EventWriter<AchievementEvent>,
+    segments: Res<SnakeSegments>,
+    arena: Res<Arena>,
+) {
+    if growth_reader.iter().next().is_some() {
+        let length = segments.0.len() as u32;
+        achievements.send(AchievementEvent::Progress {
+            stat: "length",
+            value: length,
+        });
+        achievements.send(AchievementEvent::Progress {
+            stat: "board_filled_percent",
+            value: length * 100 / (arena.width * arena.height),
+        });
+    }
+
+    if game_over_reader.iter().next().is_some() {
+        achievements.send(AchievementEvent::Trigger("game_over"));
+    }
+}
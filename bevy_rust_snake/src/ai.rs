@@ -0,0 +1,104 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{sim::Board, Direction, Position};
+
+/// Steers one snake on a `Board`.
+pub trait Controller {
+    /// Direction to turn `player` to on the next step, if any.
+    fn decide(&mut self, board: &Board, player: usize) -> Option<Direction>;
+}
+
+/// Names of the registered controllers, in the order they are listed.
+pub const CONTROLLERS: [&str; 3] = ["greedy", "random", "flood"];
+
+/// Makes the controller registered under `name`. Controllers that roll
+/// dice draw from `seed`, so a game plays out the same every time.
+pub fn controller(name: &str, seed: u64) -> Option<Box<dyn Controller>> {
+    match name {
+        "greedy" => Some(Box::new(Greedy)),
+        "random" => Some(Box::new(Random(StdRng::seed_from_u64(seed)))),
+        "flood" => Some(Box::new(Flood)),
+        _ => None,
+    }
+}
+
+/// Heads for the closest food, as long as the next cell is free. The bot
+/// standing in for a player who left an online game.
+pub struct Greedy;
+
+impl Controller for Greedy {
+    fn decide(&mut self, board: &Board, player: usize) -> Option<Direction> {
+        board.bot_direction(player)
+    }
+}
+
+/// Wanders between the moves that don't die on the next step.
+pub struct Random(StdRng);
+
+impl Controller for Random {
+    fn decide(&mut self, board: &Board, player: usize) -> Option<Direction> {
+        let snake = board.snakes.get(player).filter(|snake| snake.alive)?;
+        let head = snake.head()?;
+        let safe: Vec<Direction> = Direction::ALL
+            .iter()
+            .copied()
+            .filter(|dir| *dir != snake.direction.opposite() && board.is_free(head.moved(*dir)))
+            .collect();
+        safe.choose(&mut self.0).copied()
+    }
+}
+
+/// Like `Greedy`, but never steps into a pocket with less room than
+/// another move would leave.
+pub struct Flood;
+
+impl Flood {
+    /// Cells reachable from `start`, counted up to `limit`.
+    fn room(board: &Board, start: Position, limit: usize) -> usize {
+        if !board.is_free(start) {
+            return 0;
+        }
+        let mut seen: HashSet<Position> = vec![start].into_iter().collect();
+        let mut queue: VecDeque<Position> = vec![start].into();
+        while let Some(pos) = queue.pop_front() {
+            if seen.len() >= limit {
+                break;
+            }
+            for dir in Direction::ALL.iter() {
+                let next = pos.moved(*dir);
+                if board.is_free(next) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen.len().min(limit)
+    }
+}
+
+impl Controller for Flood {
+    fn decide(&mut self, board: &Board, player: usize) -> Option<Direction> {
+        let snake = board.snakes.get(player).filter(|snake| snake.alive)?;
+        let head = snake.head()?;
+        // enough room to fit the whole snake is as good as any more
+        let limit = snake.body.len() * 2;
+
+        Direction::ALL
+            .iter()
+            .copied()
+            .filter(|dir| *dir != snake.direction.opposite())
+            .map(|dir| {
+                let next = head.moved(dir);
+                let distance = board
+                    .food
+                    .iter()
+                    .map(|food| (food.x - next.x).abs() + (food.y - next.y).abs())
+                    .min()
+                    .unwrap_or(0);
+                (dir, Self::room(board, next, limit), distance)
+            })
+            .max_by_key(|(_, room, distance)| (*room, -distance))
+            .map(|(dir, _, _)| dir)
+    }
+}
@@ -0,0 +1,351 @@
+//! Pits the registered snake bots against each other, or lets each one play
+//! alone, over a number of seeded games on every core, then prints a
+//! leaderboard and writes it to JSON.
+//!
+//! cargo run --release --bin tournament -- [--games M] [--seed S] [--solo]
+//!     [--bots greedy,flood] [--max-ticks T] [--size N] [--threads N]
+//!     [--json FILE]
+
+use std::{
+    cmp,
+    collections::BTreeMap,
+    env, fs, mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use rust_common::stats::unix_time;
+use rust_snake::{
+    ai::{self, Controller},
+    sim::Board,
+};
+use serde::Serialize;
+
+const START_LENGTH: usize = 2;
+
+struct Config {
+    games: u32,
+    seed: u64,
+    solo: bool,
+    bots: Vec<String>,
+    max_ticks: u32,
+    size: u32,
+    threads: usize,
+    json: String,
+}
+
+impl Config {
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Self {
+            games: 100,
+            seed: 1,
+            solo: false,
+            bots: ai::CONTROLLERS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            max_ticks: 5000,
+            size: 16,
+            threads: num_cpus::get(),
+            json: "tournament.json".to_string(),
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--solo" {
+                config.solo = true;
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", arg))?;
+            let number = || format!("{} needs a number", arg);
+            match arg.as_str() {
+                "--games" => config.games = value.parse().map_err(|_| number())?,
+                "--seed" => config.seed = value.parse().map_err(|_| number())?,
+                "--max-ticks" => config.max_ticks = value.parse().map_err(|_| number())?,
+                "--size" => config.size = value.parse().map_err(|_| number())?,
+                "--threads" => config.threads = value.parse().map_err(|_| number())?,
+                "--json" => config.json = value.clone(),
+                "--bots" => config.bots = value.split(',').map(str::to_string).collect(),
+                _ => return Err(format!("unknown option: {}", arg)),
+            }
+        }
+
+        if let Some(name) = config
+            .bots
+            .iter()
+            .find(|name| ai::controller(name, 0).is_none())
+        {
+            return Err(format!(
+                "unknown bot {}, registered: {}",
+                name,
+                ai::CONTROLLERS.join(", ")
+            ));
+        }
+        if !config.solo && config.bots.len() < 2 {
+            return Err("a versus tournament needs at least two bots".to_string());
+        }
+        if config.size < 8 {
+            return Err("--size needs to be at least 8".to_string());
+        }
+        config.threads = config.threads.max(1);
+        Ok(config)
+    }
+
+    /// The bots playing each matchup: every bot alone, or every pair twice
+    /// so both get to start from either corner.
+    fn matchups(&self) -> Vec<Vec<usize>> {
+        let bots = 0..self.bots.len();
+        if self.solo {
+            bots.map(|bot| vec![bot]).collect()
+        } else {
+            bots.clone()
+                .flat_map(|a| bots.clone().map(move |b| vec![a, b]))
+                .filter(|pair| pair[0] != pair[1])
+                .collect()
+        }
+    }
+}
+
+/// How one bot did in one game.
+struct Entry {
+    bot: usize,
+    score: usize,
+    ticks: u32,
+    /// What ended its game, `None` for the last snake standing.
+    failure: Option<String>,
+}
+
+/// Plays one round of `bots` on a fresh board.
+fn play(config: &Config, bots: &[usize], seed: u64) -> Vec<Entry> {
+    let mut board = Board::new(config.size, config.size, bots.len(), seed);
+    let mut controllers: Vec<Box<dyn Controller>> = bots
+        .iter()
+        .enumerate()
+        .filter_map(|(player, bot)| ai::controller(&config.bots[*bot], seed ^ player as u64))
+        .collect();
+    let mut entries: Vec<Entry> = bots
+        .iter()
+        .map(|bot| Entry {
+            bot: *bot,
+            score: 0,
+            ticks: 0,
+            failure: None,
+        })
+        .collect();
+
+    for tick in 1..=config.max_ticks {
+        let inputs: Vec<_> = controllers
+            .iter_mut()
+            .enumerate()
+            .map(|(player, controller)| controller.decide(&board, player))
+            .collect();
+        let outcome = board.step(&inputs);
+
+        for (entry, length) in entries.iter_mut().zip(&outcome.lengths) {
+            if entry.failure.is_none() && *length > 0 {
+                entry.score = length - START_LENGTH;
+                entry.ticks = tick;
+            }
+        }
+        for death in &outcome.deaths {
+            let entry = &mut entries[death.player];
+            entry.score = death.length - START_LENGTH;
+            entry.ticks = tick;
+            entry.failure = Some(death.cause.label().to_string());
+        }
+        if outcome.round_over {
+            return entries;
+        }
+    }
+
+    for entry in entries.iter_mut().filter(|entry| entry.failure.is_none()) {
+        entry.failure = Some("timeout".to_string());
+    }
+    entries
+}
+
+#[derive(Serialize)]
+struct Standing {
+    bot: String,
+    games: usize,
+    wins: usize,
+    mean_score: f64,
+    median_score: f64,
+    mean_ticks: f64,
+    median_ticks: f64,
+    failures: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    time: u64,
+    seed: u64,
+    games: u32,
+    solo: bool,
+    max_ticks: u32,
+    size: u32,
+    leaderboard: Vec<Standing>,
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(cmp::Ordering::Equal));
+    match values.len() {
+        0 => 0.0,
+        len if len % 2 == 0 => (values[len / 2 - 1] + values[len / 2]) / 2.0,
+        len => values[len / 2],
+    }
+}
+
+fn standings(config: &Config, entries: &[Entry]) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = (0..config.bots.len())
+        .map(|bot| {
+            let played: Vec<&Entry> = entries.iter().filter(|entry| entry.bot == bot).collect();
+            let mut scores: Vec<f64> = played.iter().map(|entry| entry.score as f64).collect();
+            let mut ticks: Vec<f64> = played.iter().map(|entry| entry.ticks as f64).collect();
+            let mut failures = BTreeMap::new();
+            for failure in played.iter().filter_map(|entry| entry.failure.clone()) {
+                *failures.entry(failure).or_default() += 1;
+            }
+
+            Standing {
+                bot: config.bots[bot].clone(),
+                games: played.len(),
+                wins: played
+                    .iter()
+                    .filter(|entry| entry.failure.is_none())
+                    .count(),
+                mean_score: mean(&scores),
+                median_score: median(&mut scores),
+                mean_ticks: mean(&ticks),
+                median_ticks: median(&mut ticks),
+                failures,
+            }
+        })
+        .collect();
+
+    standings.sort_by(|a, b| {
+        b.wins.cmp(&a.wins).then(
+            b.mean_score
+                .partial_cmp(&a.mean_score)
+                .unwrap_or(cmp::Ordering::Equal),
+        )
+    });
+    standings
+}
+
+fn print_leaderboard(config: &Config, standings: &[Standing]) {
+    println!(
+        "{} games per {}, seed {}, {}x{} arena, {} ticks at most\n",
+        config.games,
+        if config.solo { "bot" } else { "pairing" },
+        config.seed,
+        config.size,
+        config.size,
+        config.max_ticks
+    );
+    println!(
+        "{:<4}{:<10}{:>6}{:>6}{:>8}{:>8}{:>10}{:>10}  failures",
+        "#", "bot", "games", "wins", "score", "median", "ticks", "median"
+    );
+    for (rank, standing) in standings.iter().enumerate() {
+        let failures: Vec<String> = standing
+            .failures
+            .iter()
+            .map(|(cause, count)| format!("{} {}", cause, count))
+            .collect();
+        println!(
+            "{:<4}{:<10}{:>6}{:>6}{:>8.1}{:>8.1}{:>10.1}{:>10.1}  {}",
+            rank + 1,
+            standing.bot,
+            standing.games,
+            standing.wins,
+            standing.mean_score,
+            standing.median_score,
+            standing.mean_ticks,
+            standing.median_ticks,
+            failures.join(", ")
+        );
+    }
+}
+
+fn run(config: Config) -> Result<(), String> {
+    let games = config.games as u64;
+    let jobs: Arc<Vec<(Vec<usize>, u64)>> = Arc::new(
+        config
+            .matchups()
+            .into_iter()
+            .flat_map(|bots| (0..games).map(move |game| (bots.clone(), game)))
+            .collect(),
+    );
+
+    // every matchup plays the same seeds, so bots are compared on the same
+    // food placements
+    let config = Arc::new(config);
+    let next = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let workers: Vec<_> = (0..config.threads.min(jobs.len()))
+        .map(|_| {
+            let (config, jobs) = (Arc::clone(&config), Arc::clone(&jobs));
+            let (next, results) = (Arc::clone(&next), Arc::clone(&results));
+            thread::spawn(move || loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                let (bots, game) = match jobs.get(job) {
+                    Some(job) => job,
+                    None => break,
+                };
+                let entries = play(&config, bots, config.seed.wrapping_add(*game));
+                results.lock().unwrap().push((job, entries));
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| "a tournament thread panicked".to_string())?;
+    }
+
+    let mut results = mem::take(&mut *results.lock().unwrap());
+    results.sort_by_key(|(job, _)| *job);
+    let entries: Vec<Entry> = results
+        .into_iter()
+        .flat_map(|(_, entries)| entries)
+        .collect();
+
+    let leaderboard = standings(&config, &entries);
+    print_leaderboard(&config, &leaderboard);
+
+    let report = Report {
+        time: unix_time(),
+        seed: config.seed,
+        games: config.games,
+        solo: config.solo,
+        max_ticks: config.max_ticks,
+        size: config.size,
+        leaderboard,
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(&config.json, json).map_err(|e| format!("{}: {}", config.json, e))?;
+    println!("\nresults written to {}", config.json);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = Config::from_args(&args).and_then(run) {
+        eprintln!("tournament: {}", e);
+        std::process::exit(1);
+    }
+}
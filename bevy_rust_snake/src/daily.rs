@@ -0,0 +1,100 @@
+use bevy::{app::AppExit, prelude::*};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rust_common::{
+    daily::{DailyChallenge, DailyLeaderboard},
+    menu::not_paused,
+};
+
+use crate::{Arena, GameOverEvent, GameRng, Modifiers, Score, SnakeMovement};
+
+const MIN_ARENA_SIZE: u32 = 10;
+const MAX_ARENA_SIZE: u32 = 16;
+
+// Resource
+pub struct DailyRun {
+    challenge: DailyChallenge,
+    leaderboard: DailyLeaderboard,
+}
+
+pub struct DailyPlugin(pub DailyChallenge);
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let challenge = self.0.clone();
+        let mut leaderboard = challenge.leaderboard();
+        if let Err(e) = leaderboard.begin_attempt(&challenge.player) {
+            warn!("could not save daily leaderboard: {}", e);
+        }
+
+        // The layout gets its own generator so the food sequence does not
+        // depend on how many numbers the layout needed.
+        let mut layout_rng = StdRng::seed_from_u64(challenge.seed);
+        let arena = Arena {
+            width: layout_rng.gen_range(MIN_ARENA_SIZE..=MAX_ARENA_SIZE),
+            height: layout_rng.gen_range(MIN_ARENA_SIZE..=MAX_ARENA_SIZE),
+        };
+        let modifiers = Modifiers {
+            wrap_walls: layout_rng.gen_bool(0.5),
+            food_per_spawn: layout_rng.gen_range(1..=2),
+        };
+        info!(
+            "daily challenge {}: {}x{} arena, wrap walls: {}, food per spawn: {}",
+            challenge.date,
+            arena.width,
+            arena.height,
+            modifiers.wrap_walls,
+            modifiers.food_per_spawn
+        );
+
+        app.insert_resource(arena)
+            .insert_resource(modifiers)
+            .insert_resource(GameRng::from_seed(challenge.seed))
+            .insert_resource(DailyRun {
+                challenge,
+                leaderboard,
+            })
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(
+                        daily_game_over
+                            .system()
+                            .after(SnakeMovement::Rules)
+                            .before(SnakeMovement::GameOver),
+                    ),
+            );
+    }
+}
+
+fn daily_game_over(
+    mut reader: EventReader<GameOverEvent>,
+    mut daily: ResMut<DailyRun>,
+    score: Res<Score>,
+    mut rng: ResMut<GameRng>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    if reader.iter().next().is_some() {
+        let DailyRun {
+            challenge,
+            leaderboard,
+        } = &mut *daily;
+
+        match leaderboard.submit(&challenge.player, score.0) {
+            Ok(rank) => info!(
+                "daily challenge {}: scored {}, rank {} of {}",
+                challenge.date,
+                score.0,
+                rank,
+                leaderboard.entries().len()
+            ),
+            Err(e) => warn!("could not save daily leaderboard: {}", e),
+        }
+
+        if challenge.one_attempt {
+            app_exit.send(AppExit);
+        } else {
+            // Every retry replays the same food sequence.
+            *rng = GameRng::from_seed(challenge.seed);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use rust_common::particles::SpawnParticles;
+
+use crate::{
+    convert, Arena, GameOverEvent, GrowthEvent, Position, SnakeHead, SnakeSegment, SnakeSegments,
+};
+
+fn world_position(pos: &Position, window: &Window, arena: &Arena) -> Vec3 {
+    Vec3::new(
+        convert(pos.x as f32, window.width(), arena.width as f32),
+        convert(pos.y as f32, window.height(), arena.height as f32),
+        1.0,
+    )
+}
+
+/// Sparks where food was eaten and debris where the snake died.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_effects(
+    mut growth_reader: EventReader<GrowthEvent>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut particles: EventWriter<SpawnParticles>,
+    windows: Res<Windows>,
+    arena: Res<Arena>,
+    segments: Res<SnakeSegments>,
+    heads: Query<&Position, With<SnakeHead>>,
+    positions: Query<&Position, With<SnakeSegment>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    if growth_reader.iter().next().is_some() {
+        // the head sits on the food it just ate
+        for head in heads.iter() {
+            particles.send(SpawnParticles {
+                emitter: "food_sparks",
+                position: world_position(head, window, &arena),
+            });
+        }
+    }
+
+    if game_over_reader.iter().next().is_some() {
+        for pos in segments.0.iter().filter_map(|e| positions.get(*e).ok()) {
+            particles.send(SpawnParticles {
+                emitter: "death_debris",
+                position: world_position(pos, window, &arena),
+            });
+        }
+    }
+}
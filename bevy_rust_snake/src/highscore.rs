@@ -0,0 +1,91 @@
+use std::fs;
+
+use bevy::log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::mode::GameMode;
+
+const HIGH_SCORE_FILE: &str = "snake_highscores.ron";
+const MAX_ENTRIES: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighScoreEntry {
+    pub score: u32,
+    /// Set when the run used rewind, so practice runs can be told apart
+    /// from clean ones.
+    #[serde(default)]
+    pub rewound: bool,
+    /// Tables from before modes existed were all endless runs.
+    #[serde(default)]
+    pub mode: GameMode,
+}
+
+// Resource
+#[derive(Serialize, Deserialize, Default)]
+pub struct HighScores {
+    entries: Vec<HighScoreEntry>,
+}
+
+impl HighScores {
+    pub fn load() -> Self {
+        fs::read_to_string(HIGH_SCORE_FILE)
+            .ok()
+            .and_then(|content| ron::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(HIGH_SCORE_FILE, content).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            warn!("could not save high scores: {}", e);
+        }
+    }
+
+    /// Inserts `entry` if it makes the table of its mode and persists the
+    /// tables.
+    pub fn submit(&mut self, entry: HighScoreEntry) {
+        if entry.score == 0 {
+            return;
+        }
+
+        let mode = entry.mode;
+        let rank = self
+            .entries
+            .iter()
+            .filter(|e| e.mode == mode)
+            .take_while(|e| e.score >= entry.score)
+            .count();
+        if rank >= MAX_ENTRIES {
+            return;
+        }
+
+        info!(
+            "new {} high score #{}: {}{}",
+            mode.name(),
+            rank + 1,
+            entry.score,
+            if entry.rewound { " (rewound)" } else { "" }
+        );
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.mode == mode && e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+        // the entry pushed off the table of this mode, if any
+        if let Some(dropped) = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.mode == mode)
+            .nth(MAX_ENTRIES)
+            .map(|(i, _)| i)
+        {
+            self.entries.remove(dropped);
+        }
+        self.save();
+    }
+}
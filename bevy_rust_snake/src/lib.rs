@@ -0,0 +1,61 @@
+//! The snake rules that run without bevy: the lockstep board, the bots
+//! steering on it and the types they share with the game.
+
+pub mod ai;
+pub mod sim;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Direction {
+    Left,
+    Up,
+    Right,
+    Down,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Up => Self::Down,
+            Self::Right => Self::Left,
+            Self::Down => Self::Up,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    /// The neighbouring cell in `dir`.
+    pub fn moved(self, dir: Direction) -> Self {
+        match dir {
+            Direction::Left => Self {
+                x: self.x - 1,
+                ..self
+            },
+            Direction::Up => Self {
+                y: self.y + 1,
+                ..self
+            },
+            Direction::Right => Self {
+                x: self.x + 1,
+                ..self
+            },
+            Direction::Down => Self {
+                y: self.y - 1,
+                ..self
+            },
+        }
+    }
+}
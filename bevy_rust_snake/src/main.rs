@@ -1,72 +1,133 @@
+mod achievements;
+mod daily;
+mod effects;
+mod highscore;
+mod mode;
+mod online;
+mod rewind;
+mod stats;
+
 use bevy::{core::FixedTimestep, prelude::*};
-use rand::random;
+use daily::{DailyPlugin, DailyRun};
+use highscore::{HighScoreEntry, HighScores};
+use mode::{GameMode, ModePlugin, ModeState};
+use online::{OnlineConfig, OnlinePlugin};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rewind::{RewindHistory, RewindPlugin};
+use rust_common::{
+    achievements::AchievementsPlugin,
+    audio::{PlaySound, SoundPlugin},
+    daily::DailyChallenge,
+    gamepad::GamepadPlugin,
+    input::{Action, ActionState, Binding, InputMapPlugin},
+    menu::{not_paused, unless_paused},
+    particles::ParticlesPlugin,
+};
+use rust_snake::{sim, Direction, Position};
+use serde::Serialize;
 
 const ARENA_WIDTH: u32 = 10;
 const ARENA_HEIGHT: u32 = 10;
+const FONT: &str = "fonts/DejaVuSans.ttf";
+const START_BODY: [Position; 2] = [Position { x: 3, y: 3 }, Position { x: 3, y: 2 }];
 
 #[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum SnakeMovement {
     Input,
+    Rewind,
     Movement,
     Eating,
     Growth,
+    Scoring,
+    Rules,
+    GameOver,
 }
 
-#[derive(PartialEq, Clone, Copy)]
-enum Direction {
-    Left,
-    Up,
-    Right,
-    Down,
+struct Size {
+    width: f32,
+    height: f32,
 }
 
-impl Direction {
-    fn opposite(self) -> Self {
-        match self {
-            Self::Left => Self::Right,
-            Self::Up => Self::Down,
-            Self::Right => Self::Left,
-            Self::Down => Self::Up,
+impl Size {
+    pub fn square(x: f32) -> Self {
+        Self {
+            width: x,
+            height: x,
         }
     }
 }
 
-#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
-struct Position {
-    x: i32,
-    y: i32,
+struct Arena {
+    width: u32,
+    height: u32,
 }
 
-struct Size {
-    width: f32,
-    height: f32,
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            width: ARENA_WIDTH,
+            height: ARENA_HEIGHT,
+        }
+    }
 }
 
-impl Size {
-    pub fn square(x: f32) -> Self {
+/// Rule tweaks on top of the classic game, used by the daily challenge.
+struct Modifiers {
+    /// Leaving the arena on one side enters it on the opposite side.
+    wrap_walls: bool,
+    food_per_spawn: u32,
+}
+
+impl Default for Modifiers {
+    fn default() -> Self {
         Self {
-            width: x,
-            height: x,
+            wrap_walls: false,
+            food_per_spawn: 1,
         }
     }
 }
 
-fn size_scaling(windows: Res<Windows>, mut q: Query<(&Size, &mut Sprite)>) {
+/// Source of all gameplay randomness, so a run can be replayed from its seed.
+struct GameRng {
+    rng: StdRng,
+    seed: u64,
+}
+
+impl GameRng {
+    fn from_seed(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+        }
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::from_seed(rand::random())
+    }
+}
+
+fn size_scaling(windows: Res<Windows>, arena: Res<Arena>, mut q: Query<(&Size, &mut Sprite)>) {
     let window = windows.get_primary().unwrap();
     for (sprite_size, mut sprite) in q.iter_mut() {
         sprite.size = Vec2::new(
-            sprite_size.width / ARENA_WIDTH as f32 * window.width() as f32,
-            sprite_size.height / ARENA_HEIGHT as f32 * window.height() as f32,
+            sprite_size.width / arena.width as f32 * window.width(),
+            sprite_size.height / arena.height as f32 * window.height(),
         );
     }
 }
 
-fn position_translation(windows: Res<Windows>, mut q: Query<(&Position, &mut Transform)>) {
+fn position_translation(
+    windows: Res<Windows>,
+    arena: Res<Arena>,
+    mut q: Query<(&Position, &mut Transform)>,
+) {
     let window = windows.get_primary().unwrap();
     for (pos, mut transform) in q.iter_mut() {
         transform.translation = Vec3::new(
-            convert(pos.x as f32, window.width() as f32, ARENA_WIDTH as f32),
-            convert(pos.y as f32, window.height() as f32, ARENA_HEIGHT as f32),
+            convert(pos.x as f32, window.width(), arena.width as f32),
+            convert(pos.y as f32, window.height(), arena.height as f32),
             0.0,
         )
     }
@@ -89,6 +150,25 @@ struct SnakeSegments(Vec<Entity>);
 #[derive(Default)]
 struct LastTailPosition(Option<Position>);
 
+#[derive(Default)]
+struct Score(u32);
+
+/// Settings chosen on the command line for the current session.
+#[derive(Default)]
+struct RunConfig {
+    /// Ranked runs count for the high-score table as-is, so practice aids
+    /// such as rewinding are disabled.
+    ranked: bool,
+}
+
+impl RunConfig {
+    fn from_args() -> Self {
+        Self {
+            ranked: std::env::args().any(|arg| arg == "--ranked" || arg == "--daily"),
+        }
+    }
+}
+
 struct Materials {
     head_material: Handle<ColorMaterial>,
     segment_material: Handle<ColorMaterial>,
@@ -97,15 +177,35 @@ struct Materials {
 
 struct GrowthEvent;
 
-struct GameOverEvent;
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+enum DeathCause {
+    Wall,
+    Tail,
+    TimeUp,
+}
+
+/// The snake ran into something; the mode decides what that costs.
+struct CrashEvent(DeathCause);
 
+/// The run is over.
+struct GameOverEvent(DeathCause);
+
+#[allow(clippy::too_many_arguments)]
 fn snake_movement(
     segments: ResMut<SnakeSegments>,
+    arena: Res<Arena>,
+    modifiers: Res<Modifiers>,
     mut heads: Query<(Entity, &SnakeHead)>,
     mut last_tail_position: ResMut<LastTailPosition>,
-    mut game_over_writer: EventWriter<GameOverEvent>,
+    mut crash_writer: EventWriter<CrashEvent>,
     mut positions: Query<&mut Position>,
+    rewind: Res<RewindHistory>,
 ) {
+    if rewind.active {
+        return;
+    }
+
     if let Some((head_entity, head)) = heads.iter_mut().next() {
         let segment_positions = segments
             .0
@@ -128,16 +228,19 @@ fn snake_movement(
             }
         };
 
-        if head_pos.x < 0
+        if modifiers.wrap_walls {
+            head_pos.x = head_pos.x.rem_euclid(arena.width as i32);
+            head_pos.y = head_pos.y.rem_euclid(arena.height as i32);
+        } else if head_pos.x < 0
             || head_pos.y < 0
-            || head_pos.x as u32 >= ARENA_WIDTH
-            || head_pos.y as u32 >= ARENA_HEIGHT
+            || head_pos.x as u32 >= arena.width
+            || head_pos.y as u32 >= arena.height
         {
-            game_over_writer.send(GameOverEvent);
+            crash_writer.send(CrashEvent(DeathCause::Wall));
         }
 
         if segment_positions.contains(&head_pos) {
-            game_over_writer.send(GameOverEvent);
+            crash_writer.send(CrashEvent(DeathCause::Tail));
         }
 
         segment_positions
@@ -151,22 +254,33 @@ fn snake_movement(
     }
 }
 
-fn snake_movement_input(keyboard_input: Res<Input<KeyCode>>, mut heads: Query<&mut SnakeHead>) {
-    if let Some(mut head) = heads.iter_mut().next() {
-        let dir: Direction = if keyboard_input.pressed(KeyCode::Left) {
-            Direction::Left
-        } else if keyboard_input.pressed(KeyCode::Down) {
-            Direction::Down
-        } else if keyboard_input.pressed(KeyCode::Up) {
-            Direction::Up
-        } else if keyboard_input.pressed(KeyCode::Right) {
-            Direction::Right
-        } else {
-            head.direction
-        };
+/// Direction the player holds, if any.
+///
+/// The most held direction wins, so a diagonal stick steers along its
+/// stronger axis; on a tie the earlier one in this list does.
+fn held_direction(actions: &ActionState) -> Option<Direction> {
+    let mut dir = None;
+    let mut held = 0.0;
+    for &(action, action_dir) in &[
+        (Action::MoveLeft, Direction::Left),
+        (Action::MoveDown, Direction::Down),
+        (Action::MoveUp, Direction::Up),
+        (Action::MoveRight, Direction::Right),
+    ] {
+        if actions.pressed(action) && actions.value(action) > held {
+            dir = Some(action_dir);
+            held = actions.value(action);
+        }
+    }
+    dir
+}
 
-        if dir != head.direction.opposite() {
-            head.direction = dir;
+fn snake_movement_input(actions: Res<ActionState>, mut heads: Query<&mut SnakeHead>) {
+    if let Some(mut head) = heads.iter_mut().next() {
+        if let Some(dir) = held_direction(&actions) {
+            if dir != head.direction.opposite() {
+                head.direction = dir;
+            }
         }
     }
 }
@@ -174,6 +288,7 @@ fn snake_movement_input(keyboard_input: Res<Input<KeyCode>>, mut heads: Query<&m
 fn snake_eating(
     mut commands: Commands,
     mut growth_writer: EventWriter<GrowthEvent>,
+    mut sounds: EventWriter<PlaySound>,
     food_positions: Query<(Entity, &Position), With<Food>>,
     head_positions: Query<&Position, With<SnakeHead>>,
 ) {
@@ -182,24 +297,27 @@ fn snake_eating(
             if food_pos == head_pos {
                 commands.entity(ent).despawn();
                 growth_writer.send(GrowthEvent);
+                sounds.send(PlaySound("eat"));
             }
         }
     }
 }
 
 fn snake_growth(
-    commands: Commands,
+    mut commands: Commands,
     last_tail_position: Res<LastTailPosition>,
     mut segments: ResMut<SnakeSegments>,
     mut growth_reader: EventReader<GrowthEvent>,
     materials: Res<Materials>,
+    mut sounds: EventWriter<PlaySound>,
 ) {
     if growth_reader.iter().next().is_some() {
         segments.0.push(spawn_segment(
-            commands,
+            &mut commands,
             &materials.segment_material,
             last_tail_position.0.unwrap(),
         ));
+        sounds.send(PlaySound("grow"));
     }
 }
 
@@ -208,30 +326,42 @@ fn spawn_snake(
     materials: Res<Materials>,
     mut segments: ResMut<SnakeSegments>,
 ) {
-    segments.0 = vec![
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.head_material.clone(),
-                sprite: Sprite::new(Vec2::new(10.0, 10.0)),
-                ..Default::default()
-            })
-            .insert(SnakeHead {
-                direction: Direction::Up,
-            })
-            .insert(SnakeSegment)
-            .insert(Position { x: 3, y: 3 })
-            .insert(Size::square(0.8))
-            .id(),
-        spawn_segment(
-            commands,
-            &materials.segment_material,
-            Position { x: 3, y: 2 },
-        ),
-    ];
+    segments.0 = spawn_snake_at(&mut commands, &materials, &START_BODY, Direction::Up);
+}
+
+/// Spawns a snake along `body`, head first.
+fn spawn_snake_at(
+    commands: &mut Commands,
+    materials: &Materials,
+    body: &[Position],
+    direction: Direction,
+) -> Vec<Entity> {
+    let (head, tail) = match body.split_first() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let head = commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.head_material.clone(),
+            sprite: Sprite::new(Vec2::new(10.0, 10.0)),
+            ..Default::default()
+        })
+        .insert(SnakeHead { direction })
+        .insert(SnakeSegment)
+        .insert(*head)
+        .insert(Size::square(0.8))
+        .id();
+
+    std::iter::once(head)
+        .chain(
+            tail.iter()
+                .map(|pos| spawn_segment(commands, &materials.segment_material, *pos)),
+        )
+        .collect()
 }
 
 fn spawn_segment(
-    mut commands: Commands,
+    commands: &mut Commands,
     material: &Handle<ColorMaterial>,
     position: Position,
 ) -> Entity {
@@ -248,29 +378,71 @@ fn spawn_segment(
 
 struct Food;
 
-fn spawn_food(mut commands: Commands, materials: Res<Materials>) {
+fn spawn_food(
+    mut commands: Commands,
+    materials: Res<Materials>,
+    rewind: Res<RewindHistory>,
+    arena: Res<Arena>,
+    modifiers: Res<Modifiers>,
+    mut rng: ResMut<GameRng>,
+) {
+    if rewind.active {
+        return;
+    }
+
+    for _ in 0..modifiers.food_per_spawn {
+        let position = Position {
+            x: rng.rng.gen_range(0..arena.width as i32),
+            y: rng.rng.gen_range(0..arena.height as i32),
+        };
+        place_food(&mut commands, &materials.food_material, position);
+    }
+}
+
+fn place_food(commands: &mut Commands, material: &Handle<ColorMaterial>, position: Position) {
     commands
         .spawn_bundle(SpriteBundle {
-            material: materials.food_material.clone(),
+            material: material.clone(),
             ..Default::default()
         })
         .insert(Food)
-        .insert(Position {
-            x: (random::<f32>() * ARENA_WIDTH as f32) as i32,
-            y: (random::<f32>() * ARENA_HEIGHT as f32) as i32,
-        })
+        .insert(position)
         .insert(Size::square(0.8));
 }
 
+#[allow(clippy::too_many_arguments)]
 fn game_over(
     mut commands: Commands,
     mut reader: EventReader<GameOverEvent>,
     materials: Res<Materials>,
     segments_res: ResMut<SnakeSegments>,
+    mut score: ResMut<Score>,
+    mut rewind: ResMut<RewindHistory>,
+    mut high_scores: ResMut<HighScores>,
+    mut rng: ResMut<GameRng>,
+    mut mode: ResMut<ModeState>,
+    daily: Option<Res<DailyRun>>,
+    mut sounds: EventWriter<PlaySound>,
     food: Query<Entity, With<Food>>,
     segments: Query<Entity, With<SnakeSegment>>,
 ) {
-    if reader.iter().next().is_some() {
+    if let Some(GameOverEvent(cause)) = reader.iter().next() {
+        if !matches!(cause, DeathCause::TimeUp) {
+            sounds.send(PlaySound("death"));
+        }
+        // Daily runs are played on their own board and go to the daily leaderboard.
+        if daily.is_none() {
+            high_scores.submit(HighScoreEntry {
+                score: score.0,
+                rewound: rewind.used,
+                mode: mode.mode,
+            });
+            *rng = GameRng::default();
+        }
+        score.0 = 0;
+        rewind.clear();
+        mode.reset();
+
         for ent in food.iter().chain(segments.iter()) {
             commands.entity(ent).despawn();
         }
@@ -280,6 +452,7 @@ fn game_over(
 
 fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(UiCameraBundle::default());
     commands.insert_resource(Materials {
         head_material: materials.add(Color::rgb(0.7, 0.7, 0.7).into()),
         segment_material: materials.add(Color::rgb(0.3, 0.3, 0.3).into()),
@@ -287,32 +460,161 @@ fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
     });
 }
 
+fn default_bindings() -> Vec<(Action, Vec<Binding>)> {
+    use Binding::{Button, Key};
+    vec![
+        (Action::MoveLeft, vec![Key(KeyCode::Left)]),
+        (Action::MoveRight, vec![Key(KeyCode::Right)]),
+        (Action::MoveUp, vec![Key(KeyCode::Up)]),
+        (Action::MoveDown, vec![Key(KeyCode::Down)]),
+        (
+            Action::Rewind,
+            vec![Key(KeyCode::Back), Button(GamepadButtonType::West)],
+        ),
+        (
+            Action::Pause,
+            vec![Key(KeyCode::Escape), Button(GamepadButtonType::Start)],
+        ),
+    ]
+}
+
 fn main() {
-    App::build()
-        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("stats") {
+        if let Err(e) = rust_common::stats::run_command("snake", &args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let online = match OnlineConfig::from_args(&args) {
+        Some(Ok(config)) => Some(config),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    let mode = match GameMode::from_args(&args) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let daily = match DailyChallenge::from_args("snake") {
+        Ok(daily) => daily,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if daily.is_some() && mode != GameMode::Endless {
+        eprintln!("the daily challenge is played in endless mode");
+        std::process::exit(1);
+    }
+
+    let mut app = App::build();
+    app.insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
         .insert_resource(WindowDescriptor {
             title: "Rust Snake".to_string(),
             width: 500.0,
             height: 500.0,
             ..Default::default()
         })
-        .insert_resource(SnakeSegments::default())
+        .insert_resource(Arena::default())
+        .add_startup_system(setup.system())
+        .add_plugin(GamepadPlugin)
+        .add_plugin(InputMapPlugin {
+            game: "snake",
+            defaults: default_bindings(),
+            font: FONT,
+        })
+        .add_plugin(SoundPlugin {
+            game: "snake",
+            sounds: &["eat", "grow", "death"],
+            font: FONT,
+        })
+        .add_plugin(ParticlesPlugin)
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .with_system(position_translation.system())
+                .with_system(size_scaling.system()),
+        )
+        .add_plugins_with(DefaultPlugins, |group| {
+            // replaced by the SoundPlugin, which copes without an audio device
+            group.disable::<bevy::audio::AudioPlugin>()
+        });
+
+    if let Some(config) = online {
+        app.add_plugin(OnlinePlugin(config));
+        app.run();
+        return;
+    }
+
+    app.insert_resource(SnakeSegments::default())
         .insert_resource(LastTailPosition::default())
+        .insert_resource(Score::default())
+        .insert_resource(RunConfig::from_args())
+        .insert_resource(HighScores::load())
+        .insert_resource(Modifiers::default())
+        .insert_resource(GameRng::default())
         .add_event::<GrowthEvent>()
+        .add_event::<CrashEvent>()
         .add_event::<GameOverEvent>()
-        .add_startup_system(setup.system())
         .add_startup_stage("game_setup", SystemStage::single(spawn_snake.system()))
-        .add_system(
-            snake_movement_input
-                .system()
-                .label(SnakeMovement::Input)
-                .before(SnakeMovement::Movement),
+        .add_plugin(RewindPlugin)
+        .add_plugin(ModePlugin(mode))
+        .add_plugin(AchievementsPlugin {
+            game: "snake",
+            font: FONT,
+        })
+        .add_plugin(stats::StatsPlugin)
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(
+                    snake_movement_input
+                        .system()
+                        .label(SnakeMovement::Input)
+                        .before(SnakeMovement::Movement),
+                )
+                .with_system(
+                    achievements::report_achievements
+                        .system()
+                        .after(SnakeMovement::Rules)
+                        .before(SnakeMovement::GameOver),
+                )
+                .with_system(
+                    effects::spawn_effects
+                        .system()
+                        .after(SnakeMovement::Rules)
+                        .before(SnakeMovement::GameOver),
+                )
+                .with_system(
+                    game_over
+                        .system()
+                        .label(SnakeMovement::GameOver)
+                        .after(SnakeMovement::Rules),
+                ),
         )
-        .add_system(game_over.system().after(SnakeMovement::Movement))
         .add_system_set(
             SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(0.150))
-                .with_system(snake_movement.system().label(SnakeMovement::Movement))
+                .with_run_criteria(FixedTimestep::step(0.150).chain(unless_paused.system()))
+                .with_system(
+                    rewind::rewind_tick
+                        .system()
+                        .label(SnakeMovement::Rewind)
+                        .before(SnakeMovement::Movement),
+                )
+                .with_system(
+                    snake_movement
+                        .system()
+                        .label(SnakeMovement::Movement)
+                        .after(SnakeMovement::Rewind),
+                )
                 .with_system(
                     snake_eating
                         .system()
@@ -328,15 +630,13 @@ fn main() {
         )
         .add_system_set(
             SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(1.0))
+                .with_run_criteria(FixedTimestep::step(1.0).chain(unless_paused.system()))
                 .with_system(spawn_food.system()),
-        )
-        .add_system_set_to_stage(
-            CoreStage::PostUpdate,
-            SystemSet::new()
-                .with_system(position_translation.system())
-                .with_system(size_scaling.system()),
-        )
-        .add_plugins(DefaultPlugins)
-        .run();
+        );
+
+    if let Some(challenge) = daily {
+        app.add_plugin(DailyPlugin(challenge));
+    }
+
+    app.run();
 }
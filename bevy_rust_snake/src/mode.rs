@@ -0,0 +1,368 @@
+use bevy::{app::AppExit, prelude::*};
+use rust_common::{audio::PlaySound, menu::not_paused};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    highscore::{HighScoreEntry, HighScores},
+    rewind::RewindHistory,
+    spawn_snake_at, CrashEvent, DeathCause, Direction, GameOverEvent, GrowthEvent,
+    LastTailPosition, Materials, Modifiers, Position, Score, SnakeHead, SnakeMovement,
+    SnakeSegment, SnakeSegments, FONT, START_BODY,
+};
+
+const TIME_LIMIT: f32 = 120.0;
+/// Points for food eaten right after the previous one, one less for every
+/// second waited.
+const FAST_FOOD_POINTS: u32 = 10;
+const CHECKPOINT_FOOD: u32 = 10;
+const CHECKPOINT_BONUS: u32 = 5;
+const MARATHON_CONTINUES: u32 = 3;
+
+/// Rule set of a run, chosen with `--mode`. Every mode has its own
+/// high-score table.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// One point per food until the first crash.
+    Endless,
+    /// As many points as possible in two minutes. Quick eating pays more
+    /// and a crash only costs the length.
+    TimeAttack,
+    /// The usual pace for as long as it lasts. Every tenth food is a
+    /// checkpoint, and a crash goes back to the last one while continues
+    /// are left.
+    Marathon,
+    /// Walls wrap and running into the tail bites it off. The score is the
+    /// length, the session ends when the game is closed.
+    Zen,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Endless
+    }
+}
+
+impl GameMode {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let i = match args.iter().position(|arg| arg == "--mode") {
+            Some(i) => i,
+            None => return Ok(Self::default()),
+        };
+        match args.get(i + 1).map(String::as_str) {
+            Some("endless") => Ok(Self::Endless),
+            Some("time-attack") => Ok(Self::TimeAttack),
+            Some("marathon") => Ok(Self::Marathon),
+            Some("zen") => Ok(Self::Zen),
+            _ => Err("--mode needs one of endless, time-attack, marathon, zen".to_string()),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Endless => "Endless",
+            Self::TimeAttack => "Time Attack",
+            Self::Marathon => "Marathon",
+            Self::Zen => "Zen",
+        }
+    }
+}
+
+/// Board to go back to after a crash in Marathon.
+struct Checkpoint {
+    body: Vec<Position>,
+    direction: Direction,
+    score: u32,
+    food_eaten: u32,
+}
+
+// Resource
+pub struct ModeState {
+    pub mode: GameMode,
+    /// Seconds played this run, pauses excluded.
+    elapsed: f32,
+    last_food_at: f32,
+    food_eaten: u32,
+    continues: u32,
+    checkpoint: Option<Checkpoint>,
+    /// Best score of the Zen session.
+    peak: u32,
+}
+
+impl ModeState {
+    fn new(mode: GameMode) -> Self {
+        Self {
+            mode,
+            elapsed: 0.0,
+            last_food_at: 0.0,
+            food_eaten: 0,
+            continues: MARATHON_CONTINUES,
+            checkpoint: None,
+            peak: 0,
+        }
+    }
+
+    /// Starts the next run in the same mode.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.mode);
+    }
+}
+
+// Component
+struct ModeStatus;
+
+pub struct ModePlugin(pub GameMode);
+
+impl Plugin for ModePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if self.0 == GameMode::Zen {
+            app.insert_resource(Modifiers {
+                wrap_walls: true,
+                ..Default::default()
+            });
+        }
+
+        app.insert_resource(ModeState::new(self.0))
+            .add_startup_system(setup_status.system())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(
+                        score_food
+                            .system()
+                            .label(SnakeMovement::Scoring)
+                            .after(SnakeMovement::Growth),
+                    )
+                    .with_system(
+                        resolve_crash
+                            .system()
+                            .label(SnakeMovement::Rules)
+                            .after(SnakeMovement::Scoring),
+                    )
+                    .with_system(
+                        run_clock
+                            .system()
+                            .label(SnakeMovement::Rules)
+                            .after(SnakeMovement::Scoring),
+                    )
+                    .with_system(show_status.system().after(SnakeMovement::GameOver)),
+            )
+            .add_system_to_stage(CoreStage::Last, submit_zen_session.system());
+    }
+}
+
+fn setup_status(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(8.0),
+                    left: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT),
+                    font_size: 16.0,
+                    color: Color::GRAY,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ModeStatus);
+}
+
+fn score_food(
+    mut growth_reader: EventReader<GrowthEvent>,
+    mut state: ResMut<ModeState>,
+    mut score: ResMut<Score>,
+    segments: Res<SnakeSegments>,
+    last_tail_position: Res<LastTailPosition>,
+    heads: Query<&SnakeHead>,
+    positions: Query<&Position, With<SnakeSegment>>,
+) {
+    if growth_reader.iter().next().is_none() {
+        return;
+    }
+    state.food_eaten += 1;
+    let checkpoint = state.mode == GameMode::Marathon && state.food_eaten % CHECKPOINT_FOOD == 0;
+
+    score.0 += match state.mode {
+        GameMode::TimeAttack => {
+            let waited = (state.elapsed - state.last_food_at) as u32;
+            state.last_food_at = state.elapsed;
+            FAST_FOOD_POINTS.saturating_sub(waited).max(1)
+        }
+        GameMode::Marathon if checkpoint => 1 + CHECKPOINT_BONUS,
+        _ => 1,
+    };
+    state.peak = state.peak.max(score.0);
+
+    if checkpoint {
+        if let Some(head) = heads.iter().next() {
+            state.checkpoint = Some(Checkpoint {
+                // the segment grown on this tick is not spawned yet
+                body: segments
+                    .0
+                    .iter()
+                    .filter_map(|e| positions.get(*e).ok().copied().or(last_tail_position.0))
+                    .collect(),
+                direction: head.direction,
+                score: score.0,
+                food_eaten: state.food_eaten,
+            });
+        }
+    }
+}
+
+fn respawn(
+    commands: &mut Commands,
+    materials: &Materials,
+    segments: &mut SnakeSegments,
+    body: &[Position],
+    direction: Direction,
+) {
+    for entity in segments.0.drain(..) {
+        commands.entity(entity).despawn();
+    }
+    segments.0 = spawn_snake_at(commands, materials, body, direction);
+}
+
+/// Decides what a crash costs in the current mode; only ends the run where
+/// the mode says so.
+#[allow(clippy::too_many_arguments)]
+fn resolve_crash(
+    mut commands: Commands,
+    mut reader: EventReader<CrashEvent>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    mut state: ResMut<ModeState>,
+    mut score: ResMut<Score>,
+    mut segments: ResMut<SnakeSegments>,
+    mut rewind: ResMut<RewindHistory>,
+    materials: Res<Materials>,
+    mut sounds: EventWriter<PlaySound>,
+    heads: Query<&Position, With<SnakeHead>>,
+    positions: Query<&Position, With<SnakeSegment>>,
+) {
+    let cause = match reader.iter().next() {
+        Some(CrashEvent(cause)) => *cause,
+        None => return,
+    };
+
+    match state.mode {
+        GameMode::Zen => {
+            let head = match heads.iter().next() {
+                Some(head) => *head,
+                None => return,
+            };
+            // the head now shares its cell with the segment it ran into
+            let bitten = segments
+                .0
+                .iter()
+                .skip(1)
+                .position(|e| positions.get(*e).ok() == Some(&head));
+            if let Some(i) = bitten {
+                let lost = segments.0.len() - (i + 1);
+                for entity in segments.0.drain(i + 1..) {
+                    commands.entity(entity).despawn();
+                }
+                score.0 = score.0.saturating_sub(lost as u32);
+                rewind.forget();
+                sounds.send(PlaySound("death"));
+            }
+        }
+        GameMode::TimeAttack => {
+            respawn(
+                &mut commands,
+                &materials,
+                &mut segments,
+                &START_BODY,
+                Direction::Up,
+            );
+            rewind.forget();
+            sounds.send(PlaySound("death"));
+        }
+        GameMode::Marathon if state.continues > 0 => {
+            state.continues -= 1;
+            state.food_eaten = match &state.checkpoint {
+                Some(checkpoint) => {
+                    respawn(
+                        &mut commands,
+                        &materials,
+                        &mut segments,
+                        &checkpoint.body,
+                        checkpoint.direction,
+                    );
+                    score.0 = checkpoint.score;
+                    checkpoint.food_eaten
+                }
+                None => {
+                    respawn(
+                        &mut commands,
+                        &materials,
+                        &mut segments,
+                        &START_BODY,
+                        Direction::Up,
+                    );
+                    score.0 = 0;
+                    0
+                }
+            };
+            rewind.forget();
+            sounds.send(PlaySound("death"));
+        }
+        GameMode::Marathon | GameMode::Endless => game_over_writer.send(GameOverEvent(cause)),
+    }
+}
+
+fn run_clock(
+    time: Res<Time>,
+    mut state: ResMut<ModeState>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+) {
+    state.elapsed += time.delta_seconds();
+    if state.mode == GameMode::TimeAttack && state.elapsed >= TIME_LIMIT {
+        // the run is reset before the next frame
+        game_over_writer.send(GameOverEvent(DeathCause::TimeUp));
+    }
+}
+
+fn show_status(
+    state: Res<ModeState>,
+    score: Res<Score>,
+    mut texts: Query<&mut Text, With<ModeStatus>>,
+) {
+    let detail = match state.mode {
+        GameMode::TimeAttack => {
+            let left = (TIME_LIMIT - state.elapsed).max(0.0).ceil() as u32;
+            format!("   {}:{:02}", left / 60, left % 60)
+        }
+        GameMode::Marathon => format!("   continues: {}", state.continues),
+        GameMode::Endless | GameMode::Zen => String::new(),
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("{}{}   score: {}", state.mode.name(), detail, score.0);
+    }
+}
+
+/// Zen has no game over, so its best score goes to the table when the game
+/// is closed.
+fn submit_zen_session(
+    mut exit_reader: EventReader<AppExit>,
+    state: Res<ModeState>,
+    rewind: Res<RewindHistory>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    if state.mode == GameMode::Zen && exit_reader.iter().next().is_some() {
+        high_scores.submit(HighScoreEntry {
+            score: state.peak,
+            rewound: rewind.used,
+            mode: GameMode::Zen,
+        });
+    }
+}
@@ -0,0 +1,627 @@
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    net::{SocketAddr, UdpSocket},
+};
+
+use bevy::{core::FixedTimestep, input::InputSystem, prelude::*};
+use rust_common::{
+    input::ActionState,
+    menu::{not_paused, unless_paused},
+};
+
+use crate::{held_direction, sim::Board, Arena, Direction, Materials, Position, Size, FONT};
+
+const DEFAULT_PORT: u16 = 7777;
+const DEFAULT_INPUT_DELAY: u32 = 3;
+const ARENA_SIZE: u32 = 16;
+const TICK_SECONDS: f64 = 0.150;
+/// Silence after which the peer counts as gone and a bot takes over.
+const DISCONNECT_SECONDS: f64 = 3.0;
+/// Ticks of input repeated in every packet, so a lost packet costs nothing.
+const INPUT_REDUNDANCY: u32 = 8;
+/// Ticks of state hashes kept around to compare with the peer's.
+const HASH_HISTORY: u32 = 64;
+/// Bumped whenever the packets or the board hash change.
+const MAGIC: &[u8; 4] = b"SNK2";
+/// Hashed tick sent before the first tick ran.
+const NO_HASH: u32 = u32::MAX;
+
+pub enum Role {
+    Host { port: u16 },
+    Join { addr: SocketAddr },
+}
+
+/// Command line of the `host` and `join` subcommands.
+pub struct OnlineConfig {
+    role: Role,
+    input_delay: u32,
+}
+
+impl OnlineConfig {
+    /// `host [PORT] [--delay TICKS]` or `join ADDR [--delay TICKS]`, `None`
+    /// when the game is not started in online mode.
+    pub fn from_args(args: &[String]) -> Option<Result<Self, String>> {
+        let role = match args.get(1).map(String::as_str) {
+            Some("host") => match args.get(2).filter(|arg| !arg.starts_with("--")) {
+                Some(port) => port
+                    .parse()
+                    .map(|port| Role::Host { port })
+                    .map_err(|_| format!("invalid port: {}", port)),
+                None => Ok(Role::Host { port: DEFAULT_PORT }),
+            },
+            Some("join") => match args.get(2) {
+                Some(addr) => addr
+                    .parse()
+                    .map(|addr| Role::Join { addr })
+                    .map_err(|_| format!("invalid address: {}", addr)),
+                None => Err("join needs the host address, e.g. 127.0.0.1:7777".to_string()),
+            },
+            _ => return None,
+        };
+
+        let input_delay = match args.iter().position(|arg| arg == "--delay") {
+            Some(i) => match args.get(i + 1).and_then(|ticks| ticks.parse().ok()) {
+                Some(ticks) => Ok(ticks),
+                None => Err("--delay needs a number of ticks".to_string()),
+            },
+            None => Ok(DEFAULT_INPUT_DELAY),
+        };
+
+        Some(role.and_then(|role| {
+            Ok(Self {
+                role,
+                input_delay: input_delay?,
+            })
+        }))
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum Message {
+    /// Sent by the joining peer until the host answers.
+    Hello,
+    /// The host's answer, with everything needed to build the same board.
+    Welcome { seed: u64, input_delay: u32 },
+    /// The sender's inputs for ticks `first_tick..`, and the hash of its
+    /// board after its latest tick.
+    Inputs {
+        first_tick: u32,
+        directions: Vec<Option<Direction>>,
+        hashed_tick: u32,
+        hash: u64,
+    },
+}
+
+fn encode_direction(dir: Option<Direction>) -> u8 {
+    match dir {
+        None => 0,
+        Some(Direction::Left) => 1,
+        Some(Direction::Up) => 2,
+        Some(Direction::Right) => 3,
+        Some(Direction::Down) => 4,
+    }
+}
+
+fn decode_direction(byte: u8) -> Option<Option<Direction>> {
+    match byte {
+        0 => Some(None),
+        1 => Some(Some(Direction::Left)),
+        2 => Some(Some(Direction::Up)),
+        3 => Some(Some(Direction::Right)),
+        4 => Some(Some(Direction::Down)),
+        _ => None,
+    }
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        match self {
+            Message::Hello => bytes.push(0),
+            Message::Welcome { seed, input_delay } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&seed.to_le_bytes());
+                bytes.extend_from_slice(&input_delay.to_le_bytes());
+            }
+            Message::Inputs {
+                first_tick,
+                directions,
+                hashed_tick,
+                hash,
+            } => {
+                bytes.push(2);
+                bytes.extend_from_slice(&first_tick.to_le_bytes());
+                bytes.extend_from_slice(&hashed_tick.to_le_bytes());
+                bytes.extend_from_slice(&hash.to_le_bytes());
+                bytes.extend(directions.iter().map(|dir| encode_direction(*dir)));
+            }
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.strip_prefix(MAGIC)?;
+        let (kind, rest) = bytes.split_first()?;
+        let u32_at = |at: usize| Some(u32::from_le_bytes(rest.get(at..at + 4)?.try_into().ok()?));
+        let u64_at = |at: usize| Some(u64::from_le_bytes(rest.get(at..at + 8)?.try_into().ok()?));
+
+        match kind {
+            0 => Some(Message::Hello),
+            1 => Some(Message::Welcome {
+                seed: u64_at(0)?,
+                input_delay: u32_at(8)?,
+            }),
+            2 => Some(Message::Inputs {
+                first_tick: u32_at(0)?,
+                hashed_tick: u32_at(4)?,
+                hash: u64_at(8)?,
+                directions: rest
+                    .get(16..)?
+                    .iter()
+                    .map(|byte| decode_direction(*byte))
+                    .collect::<Option<_>>()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Status {
+    Connecting,
+    Playing,
+    /// The peer went silent, its snake is steered by a bot from now on.
+    PeerLeft,
+    Desync {
+        tick: u32,
+    },
+}
+
+// Resource
+/// Network side of an online game: who the peer is, the inputs both sides
+/// scheduled and the board hashes to compare.
+struct Lockstep {
+    socket: UdpSocket,
+    peer: Option<SocketAddr>,
+    /// Index of the local snake on the board, the host plays the first one.
+    local_player: usize,
+    seed: u64,
+    input_delay: u32,
+    status: Status,
+    last_heard: f64,
+    /// Direction pressed since the last tick.
+    pending: Option<Direction>,
+    local_inputs: BTreeMap<u32, Option<Direction>>,
+    remote_inputs: BTreeMap<u32, Option<Direction>>,
+    local_hashes: BTreeMap<u32, u64>,
+    remote_hashes: BTreeMap<u32, u64>,
+}
+
+impl Lockstep {
+    fn send(&self, message: &Message) {
+        if let Some(peer) = self.peer {
+            if let Err(e) = self.socket.send_to(&message.encode(), peer) {
+                warn!("could not send to {}: {}", peer, e);
+            }
+        }
+    }
+
+    /// Starts the game once both sides agree on the board.
+    fn start(&mut self, commands: &mut Commands, seed: u64, input_delay: u32) {
+        self.seed = seed;
+        self.input_delay = input_delay;
+        // nobody can have pressed anything for the ticks before the delay
+        for tick in 0..input_delay {
+            self.local_inputs.insert(tick, None);
+            self.remote_inputs.insert(tick, None);
+        }
+        self.status = Status::Playing;
+        commands.insert_resource(Board::new(ARENA_SIZE, ARENA_SIZE, 2, seed));
+        info!(
+            "online game started, seed {}, input delay {} ticks",
+            seed, input_delay
+        );
+    }
+
+    fn check_hash(&mut self, tick: u32) {
+        if let (Some(local), Some(remote)) =
+            (self.local_hashes.get(&tick), self.remote_hashes.get(&tick))
+        {
+            if local != remote && self.status == Status::Playing {
+                error!("boards diverged at tick {}", tick);
+                self.status = Status::Desync { tick };
+            }
+        }
+    }
+}
+
+// Component
+struct BoardSprite;
+
+// Component
+struct StatusText;
+
+// Resource
+struct OnlineMaterials {
+    peer_head: Handle<ColorMaterial>,
+    peer_segment: Handle<ColorMaterial>,
+}
+
+/// Two player snake over UDP, in lockstep.
+///
+/// Each peer sends the direction pressed on a tick for `input_delay` ticks
+/// later, and a tick only runs once both inputs for it are in, so both
+/// boards see the same inputs on the same ticks. The peers also exchange
+/// board hashes to detect when they diverge anyway.
+pub struct OnlinePlugin(pub OnlineConfig);
+
+impl Plugin for OnlinePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let OnlineConfig { role, input_delay } = &self.0;
+        let (bind, peer, local_player) = match role {
+            Role::Host { port } => (SocketAddr::from(([0, 0, 0, 0], *port)), None, 0),
+            Role::Join { addr } => (SocketAddr::from(([0, 0, 0, 0], 0)), Some(*addr), 1),
+        };
+        let socket = UdpSocket::bind(bind)
+            .and_then(|socket| socket.set_nonblocking(true).map(|_| socket))
+            .unwrap_or_else(|e| {
+                eprintln!("could not open UDP socket on {}: {}", bind, e);
+                std::process::exit(1);
+            });
+        match role {
+            Role::Host { port } => info!("hosting on port {}, waiting for a peer", port),
+            Role::Join { addr } => info!("joining {}", addr),
+        }
+
+        app.insert_resource(Arena {
+            width: ARENA_SIZE,
+            height: ARENA_SIZE,
+        })
+        .insert_resource(Lockstep {
+            socket,
+            peer,
+            local_player,
+            seed: 0,
+            input_delay: *input_delay,
+            status: Status::Connecting,
+            last_heard: 0.0,
+            pending: None,
+            local_inputs: BTreeMap::new(),
+            remote_inputs: BTreeMap::new(),
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+        })
+        .add_startup_system(online_setup.system())
+        // the peer is kept up to date even while the game is paused
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            exchange_packets.system().after(InputSystem),
+        )
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(not_paused.system())
+                .with_system(online_input.system().label("online_input"))
+                .with_system(draw_board.system())
+                .with_system(show_status.system()),
+        )
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(FixedTimestep::step(TICK_SECONDS).chain(unless_paused.system()))
+                .with_system(online_tick.system().after("online_input")),
+        );
+    }
+}
+
+fn online_setup(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(OnlineMaterials {
+        peer_head: materials.add(Color::rgb(0.9, 0.6, 0.2).into()),
+        peer_segment: materials.add(Color::rgb(0.5, 0.3, 0.1).into()),
+    });
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(StatusText);
+}
+
+fn exchange_packets(mut commands: Commands, mut lockstep: ResMut<Lockstep>, time: Res<Time>) {
+    let now = time.seconds_since_startup();
+    let mut buf = [0; 512];
+
+    loop {
+        let (len, from) = match lockstep.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("could not receive: {}", e);
+                break;
+            }
+        };
+        let message = match Message::decode(&buf[..len]) {
+            Some(message) => message,
+            None => continue,
+        };
+        if lockstep.peer.is_some() && lockstep.peer != Some(from) {
+            // only one peer per game
+            continue;
+        }
+        lockstep.last_heard = now;
+
+        match message {
+            Message::Hello if lockstep.local_player == 0 => {
+                if lockstep.status == Status::Connecting {
+                    info!("{} joined", from);
+                    lockstep.peer = Some(from);
+                    let input_delay = lockstep.input_delay;
+                    lockstep.start(&mut commands, rand::random(), input_delay);
+                }
+                // answered every time, in case an earlier welcome got lost
+                lockstep.send(&Message::Welcome {
+                    seed: lockstep.seed,
+                    input_delay: lockstep.input_delay,
+                });
+            }
+            Message::Welcome { seed, input_delay } if lockstep.status == Status::Connecting => {
+                lockstep.start(&mut commands, seed, input_delay);
+            }
+            Message::Inputs {
+                first_tick,
+                directions,
+                hashed_tick,
+                hash,
+            } => {
+                for (tick, dir) in (first_tick..).zip(directions) {
+                    lockstep.remote_inputs.entry(tick).or_insert(dir);
+                }
+                if hashed_tick != NO_HASH {
+                    lockstep.remote_hashes.insert(hashed_tick, hash);
+                    lockstep.check_hash(hashed_tick);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match lockstep.status {
+        Status::Connecting => {
+            if lockstep.local_player == 1 {
+                lockstep.send(&Message::Hello);
+            }
+        }
+        Status::Playing if now - lockstep.last_heard > DISCONNECT_SECONDS => {
+            info!("peer disconnected, a bot takes over its snake");
+            lockstep.status = Status::PeerLeft;
+        }
+        Status::Playing | Status::Desync { .. } => {
+            let newest = lockstep
+                .local_inputs
+                .keys()
+                .next_back()
+                .copied()
+                .unwrap_or(0);
+            let first_tick = newest.saturating_sub(INPUT_REDUNDANCY - 1);
+            let (hashed_tick, hash) = lockstep
+                .local_hashes
+                .iter()
+                .next_back()
+                .map_or((NO_HASH, 0), |(tick, hash)| (*tick, *hash));
+            let message = Message::Inputs {
+                first_tick,
+                directions: lockstep
+                    .local_inputs
+                    .range(first_tick..)
+                    .map(|(_, dir)| *dir)
+                    .collect(),
+                hashed_tick,
+                hash,
+            };
+            lockstep.send(&message);
+        }
+        Status::PeerLeft => {}
+    }
+}
+
+fn online_input(actions: Res<ActionState>, mut lockstep: ResMut<Lockstep>) {
+    if let Some(dir) = held_direction(&actions) {
+        lockstep.pending = Some(dir);
+    }
+}
+
+fn online_tick(mut lockstep: ResMut<Lockstep>, board: Option<ResMut<Board>>) {
+    let mut board = match board {
+        Some(board) => board,
+        None => return,
+    };
+    if !matches!(lockstep.status, Status::Playing | Status::PeerLeft) {
+        return;
+    }
+
+    let tick = board.tick;
+    let scheduled = tick + lockstep.input_delay;
+    if !lockstep.local_inputs.contains_key(&scheduled) {
+        let pending = lockstep.pending.take();
+        lockstep.local_inputs.insert(scheduled, pending);
+    }
+
+    let peer = 1 - lockstep.local_player;
+    let peer_input = if lockstep.status == Status::PeerLeft {
+        board.bot_direction(peer)
+    } else {
+        match lockstep.remote_inputs.get(&tick) {
+            Some(dir) => *dir,
+            // stall until the peer's input for this tick arrives
+            None => return,
+        }
+    };
+
+    let mut inputs = [None; 2];
+    inputs[lockstep.local_player] = lockstep.local_inputs[&tick];
+    inputs[peer] = peer_input;
+    board.step(&inputs);
+
+    lockstep.local_hashes.insert(tick, board.hash());
+    lockstep.check_hash(tick);
+
+    let keep_from = tick.saturating_sub(HASH_HISTORY);
+    lockstep.local_inputs = lockstep.local_inputs.split_off(&keep_from);
+    lockstep.remote_inputs = lockstep.remote_inputs.split_off(&keep_from);
+    lockstep.local_hashes = lockstep.local_hashes.split_off(&keep_from);
+    lockstep.remote_hashes = lockstep.remote_hashes.split_off(&keep_from);
+}
+
+fn draw_board(
+    mut commands: Commands,
+    board: Option<Res<Board>>,
+    lockstep: Res<Lockstep>,
+    materials: Res<Materials>,
+    online_materials: Res<OnlineMaterials>,
+    sprites: Query<Entity, With<BoardSprite>>,
+) {
+    let board = match board {
+        Some(board) if board.is_changed() => board,
+        _ => return,
+    };
+    for entity in sprites.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let mut spawn = |material: &Handle<ColorMaterial>, position: Position, size: f32| {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: material.clone(),
+                ..Default::default()
+            })
+            .insert(BoardSprite)
+            .insert(position)
+            .insert(Size::square(size));
+    };
+
+    for (player, snake) in board.snakes.iter().enumerate() {
+        let (head, segment) = if player == lockstep.local_player {
+            (&materials.head_material, &materials.segment_material)
+        } else {
+            (&online_materials.peer_head, &online_materials.peer_segment)
+        };
+        for (i, pos) in snake.body.iter().enumerate() {
+            if i == 0 {
+                spawn(head, *pos, 0.8);
+            } else {
+                spawn(segment, *pos, 0.65);
+            }
+        }
+    }
+    for pos in board.food.iter() {
+        spawn(&materials.food_material, *pos, 0.8);
+    }
+}
+
+fn show_status(
+    lockstep: Res<Lockstep>,
+    board: Option<Res<Board>>,
+    mut texts: Query<&mut Text, With<StatusText>>,
+) {
+    let status = match (&lockstep.status, board) {
+        (Status::Desync { tick }, _) => format!("Boards diverged at tick {}, game stopped", tick),
+        (Status::Playing, Some(board)) | (Status::PeerLeft, Some(board)) => {
+            let peer = 1 - lockstep.local_player;
+            let score = format!(
+                "You {} - {} Opponent",
+                board.wins[lockstep.local_player], board.wins[peer]
+            );
+            if lockstep.status == Status::PeerLeft {
+                score + "   (opponent left, a bot plays for them)"
+            } else if !lockstep.remote_inputs.contains_key(&board.tick) {
+                score + "   (waiting for opponent)"
+            } else {
+                score
+            }
+        }
+        _ if lockstep.local_player == 0 => "Waiting for a player to join".to_string(),
+        _ => "Connecting...".to_string(),
+    };
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_survive_encoding() {
+        let messages = [
+            Message::Hello,
+            Message::Welcome {
+                seed: 0x0123_4567_89ab_cdef,
+                input_delay: 3,
+            },
+            Message::Inputs {
+                first_tick: 70_000,
+                directions: vec![
+                    None,
+                    Some(Direction::Left),
+                    Some(Direction::Up),
+                    Some(Direction::Right),
+                    Some(Direction::Down),
+                ],
+                hashed_tick: NO_HASH,
+                hash: u64::MAX - 1,
+            },
+            Message::Inputs {
+                first_tick: 0,
+                directions: Vec::new(),
+                hashed_tick: 0,
+                hash: 0,
+            },
+        ];
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn foreign_and_broken_packets_are_dropped() {
+        let inputs = Message::Inputs {
+            first_tick: 5,
+            directions: vec![Some(Direction::Up)],
+            hashed_tick: 4,
+            hash: 99,
+        }
+        .encode();
+
+        // a hello from a build with the old board hash
+        assert_eq!(Message::decode(b"SNK1\0"), None);
+        assert_eq!(Message::decode(&inputs[..inputs.len() - 2]), None);
+        let mut bad_direction = inputs.clone();
+        *bad_direction.last_mut().unwrap() = 9;
+        assert_eq!(Message::decode(&bad_direction), None);
+        assert_eq!(Message::decode(&[&MAGIC[..], &[7]].concat()), None);
+    }
+}
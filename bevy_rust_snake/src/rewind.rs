@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rust_common::{
+    input::{Action, ActionState},
+    menu::not_paused,
+};
+
+use crate::{
+    place_food, Direction, Food, Materials, Position, RunConfig, Score, SnakeHead, SnakeSegments,
+};
+
+// ~6 seconds at one snapshot per movement tick
+const MAX_SNAPSHOTS: usize = 40;
+
+/// State of the board at the start of a movement tick.
+#[derive(Clone)]
+struct Snapshot {
+    segments: Vec<Position>,
+    direction: Direction,
+    food: Vec<Position>,
+    score: u32,
+}
+
+// Resource
+#[derive(Default)]
+pub struct RewindHistory {
+    snapshots: VecDeque<Snapshot>,
+    requested: bool,
+    pub active: bool,
+    pub used: bool,
+}
+
+impl RewindHistory {
+    fn record(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn clear(&mut self) {
+        self.forget();
+        self.used = false;
+    }
+
+    /// Drops the recorded ticks when the board was reset mid-run, as they
+    /// no longer lead up to it.
+    pub fn forget(&mut self) {
+        self.snapshots.clear();
+        self.active = false;
+    }
+}
+
+pub struct RewindPlugin;
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(RewindHistory::default())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(rewind_input.system()),
+            );
+    }
+}
+
+fn rewind_input(
+    actions: Res<ActionState>,
+    run_config: Res<RunConfig>,
+    mut history: ResMut<RewindHistory>,
+) {
+    history.requested = !run_config.ranked && actions.pressed(Action::Rewind);
+}
+
+/// Runs at the start of every movement tick: either steps the board back by
+/// one tick, or records the current board so it can be stepped back to later.
+#[allow(clippy::too_many_arguments)]
+pub fn rewind_tick(
+    mut commands: Commands,
+    mut history: ResMut<RewindHistory>,
+    mut segments: ResMut<SnakeSegments>,
+    mut score: ResMut<Score>,
+    materials: Res<Materials>,
+    mut heads: Query<&mut SnakeHead>,
+    mut positions: Query<&mut Position, Without<Food>>,
+    food: Query<(Entity, &Position), With<Food>>,
+) {
+    let head = match heads.iter_mut().next() {
+        Some(head) => head,
+        None => return,
+    };
+
+    if history.requested {
+        if let Some(snapshot) = history.snapshots.pop_back() {
+            restore(
+                &mut commands,
+                &materials,
+                &snapshot,
+                &mut segments,
+                head,
+                &mut positions,
+                &food,
+            );
+            score.0 = snapshot.score;
+            history.active = true;
+            history.used = true;
+            return;
+        }
+    }
+    history.active = false;
+
+    let snapshot = Snapshot {
+        segments: segments
+            .0
+            .iter()
+            .filter_map(|e| positions.get_mut(*e).ok().map(|pos| *pos))
+            .collect(),
+        direction: head.direction,
+        food: food.iter().map(|(_, pos)| *pos).collect(),
+        score: score.0,
+    };
+    history.record(snapshot);
+}
+
+fn restore(
+    commands: &mut Commands,
+    materials: &Materials,
+    snapshot: &Snapshot,
+    segments: &mut SnakeSegments,
+    mut head: Mut<SnakeHead>,
+    positions: &mut Query<&mut Position, Without<Food>>,
+    food: &Query<(Entity, &Position), With<Food>>,
+) {
+    head.direction = snapshot.direction;
+
+    // The snake can only have been shorter in the past, so drop the newest tail segments.
+    for entity in segments
+        .0
+        .drain(snapshot.segments.len().min(segments.0.len())..)
+    {
+        commands.entity(entity).despawn();
+    }
+    for (entity, pos) in segments.0.iter().zip(snapshot.segments.iter()) {
+        if let Ok(mut current) = positions.get_mut(*entity) {
+            *current = *pos;
+        }
+    }
+
+    for (entity, _) in food.iter() {
+        commands.entity(entity).despawn();
+    }
+    for pos in snapshot.food.iter() {
+        place_food(commands, &materials.food_material, *pos);
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rust_common::hash::Fnv1a;
+
+use crate::{Direction, Position};
+
+/// A snake on a `Board`, head first.
+#[derive(Clone)]
+pub struct BoardSnake {
+    pub body: VecDeque<Position>,
+    pub direction: Direction,
+    pub alive: bool,
+}
+
+impl BoardSnake {
+    pub fn head(&self) -> Option<Position> {
+        self.body.front().copied()
+    }
+}
+
+/// What a snake died of.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DeathCause {
+    Wall,
+    OwnBody,
+    OtherSnake,
+    /// Two heads moved onto the same cell.
+    HeadOn,
+}
+
+impl DeathCause {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Wall => "wall",
+            Self::OwnBody => "own_body",
+            Self::OtherSnake => "other_snake",
+            Self::HeadOn => "head_on",
+        }
+    }
+}
+
+/// A snake that died on a step.
+pub struct Death {
+    pub player: usize,
+    pub cause: DeathCause,
+    /// Length right before dying.
+    pub length: usize,
+}
+
+/// What happened on a step.
+#[derive(Default)]
+pub struct StepOutcome {
+    pub deaths: Vec<Death>,
+    /// Length of each snake after the step, taken before a finished round
+    /// resets the board.
+    pub lengths: Vec<usize>,
+    /// The round ended with this step and the board has been reset.
+    pub round_over: bool,
+}
+
+/// Several snakes sharing one arena, simulated as plain data.
+///
+/// Given the same seed and the same inputs every tick, two boards stay
+/// identical, which is what lockstep play relies on.
+pub struct Board {
+    pub width: i32,
+    pub height: i32,
+    pub snakes: Vec<BoardSnake>,
+    pub food: Vec<Position>,
+    /// Rounds won by each snake.
+    pub wins: Vec<u32>,
+    pub tick: u32,
+    rng: StdRng,
+}
+
+impl Board {
+    pub fn new(width: u32, height: u32, players: usize, seed: u64) -> Self {
+        let mut board = Self {
+            width: width as i32,
+            height: height as i32,
+            snakes: Vec::new(),
+            food: Vec::new(),
+            wins: vec![0; players],
+            tick: 0,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        board.new_round();
+        board
+    }
+
+    /// Puts every snake back at its start, alternating between the lower
+    /// left corner heading up and the upper right corner heading down.
+    fn new_round(&mut self) {
+        let players = self.wins.len() as i32;
+        self.snakes = (0..players)
+            .map(|player| {
+                let column = 2 + player / 2 * 2;
+                let (head, tail, direction) = if player % 2 == 0 {
+                    (
+                        Position { x: column, y: 2 },
+                        Position { x: column, y: 1 },
+                        Direction::Up,
+                    )
+                } else {
+                    let x = self.width - 1 - column;
+                    (
+                        Position {
+                            x,
+                            y: self.height - 3,
+                        },
+                        Position {
+                            x,
+                            y: self.height - 2,
+                        },
+                        Direction::Down,
+                    )
+                };
+                BoardSnake {
+                    body: vec![head, tail].into(),
+                    direction,
+                    alive: true,
+                }
+            })
+            .collect();
+        self.food.clear();
+        self.spawn_food();
+    }
+
+    pub fn in_bounds(&self, pos: Position) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width && pos.y < self.height
+    }
+
+    /// Whether `pos` is inside the arena and clear of every snake.
+    pub fn is_free(&self, pos: Position) -> bool {
+        self.in_bounds(pos) && !self.snakes.iter().any(|snake| snake.body.contains(&pos))
+    }
+
+    fn spawn_food(&mut self) {
+        let free: Vec<Position> = (0..self.width)
+            .flat_map(|x| (0..self.height).map(move |y| Position { x, y }))
+            .filter(|pos| self.is_free(*pos) && !self.food.contains(pos))
+            .collect();
+        if !free.is_empty() {
+            let pos = free[self.rng.gen_range(0..free.len())];
+            self.food.push(pos);
+        }
+    }
+
+    /// Advances one tick. `inputs` holds the direction each snake turns to,
+    /// if any; turning back onto itself is ignored.
+    pub fn step(&mut self, inputs: &[Option<Direction>]) -> StepOutcome {
+        for (snake, input) in self.snakes.iter_mut().zip(inputs) {
+            if let Some(dir) = input {
+                if *dir != snake.direction.opposite() {
+                    snake.direction = *dir;
+                }
+            }
+        }
+
+        let heads: Vec<Option<Position>> = self
+            .snakes
+            .iter()
+            .map(|snake| {
+                snake
+                    .head()
+                    .filter(|_| snake.alive)
+                    .map(|head| head.moved(snake.direction))
+            })
+            .collect();
+        let grows: Vec<bool> = heads
+            .iter()
+            .map(|head| head.map_or(false, |head| self.food.contains(&head)))
+            .collect();
+
+        let causes: Vec<Option<DeathCause>> = heads
+            .iter()
+            .enumerate()
+            .map(|(i, head)| {
+                let head = (*head)?;
+                let hits_body = |j: usize| {
+                    let other = &self.snakes[j];
+                    // a tail that moves on this tick is not in the way
+                    let len = other.body.len() - (other.alive && !grows[j]) as usize;
+                    other.body.iter().take(len).any(|pos| *pos == head)
+                };
+
+                if !self.in_bounds(head) {
+                    Some(DeathCause::Wall)
+                } else if hits_body(i) {
+                    Some(DeathCause::OwnBody)
+                } else if (0..self.snakes.len()).any(|j| j != i && hits_body(j)) {
+                    Some(DeathCause::OtherSnake)
+                } else if (0..heads.len()).any(|j| j != i && heads[j] == Some(head)) {
+                    Some(DeathCause::HeadOn)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut outcome = StepOutcome::default();
+        for (i, snake) in self.snakes.iter_mut().enumerate() {
+            if let Some(cause) = causes[i] {
+                outcome.deaths.push(Death {
+                    player: i,
+                    cause,
+                    length: snake.body.len(),
+                });
+                snake.alive = false;
+                snake.body.clear();
+            } else if let Some(head) = heads[i] {
+                snake.body.push_front(head);
+                if grows[i] {
+                    self.food.retain(|food| *food != head);
+                } else {
+                    snake.body.pop_back();
+                }
+            }
+        }
+        if grows
+            .iter()
+            .zip(causes.iter())
+            .any(|(grew, cause)| *grew && cause.is_none())
+        {
+            self.spawn_food();
+        }
+
+        self.tick += 1;
+        outcome.lengths = self.snakes.iter().map(|snake| snake.body.len()).collect();
+
+        let alive: Vec<usize> = (0..self.snakes.len())
+            .filter(|i| self.snakes[*i].alive)
+            .collect();
+        // a round lasts as long as there is more than one snake left, or
+        // the only one when playing alone
+        if alive.len() <= 1 && self.snakes.len() > 1 || alive.is_empty() {
+            if let [winner] = alive[..] {
+                self.wins[winner] += 1;
+            }
+            self.new_round();
+            outcome.round_over = true;
+        }
+        outcome
+    }
+
+    /// Hash of everything the peers have to agree on. It is the same on
+    /// every build, so peers built with different toolchains can compare it.
+    pub fn hash(&self) -> u64 {
+        let mut hash = Fnv1a::default();
+        hash.write_u32(self.tick);
+        for snake in self.snakes.iter() {
+            hash.write(&[snake.alive as u8, snake.direction as u8]);
+            write_positions(&mut hash, snake.body.iter());
+        }
+        write_positions(&mut hash, self.food.iter());
+        for wins in self.wins.iter() {
+            hash.write_u32(*wins);
+        }
+        hash.finish()
+    }
+
+    /// Direction a simple bot would steer `player` to: towards the closest
+    /// food, without running into a wall or a snake on the next tick.
+    pub fn bot_direction(&self, player: usize) -> Option<Direction> {
+        let snake = self.snakes.get(player).filter(|snake| snake.alive)?;
+
+        Direction::ALL
+            .iter()
+            .copied()
+            .filter(|dir| *dir != snake.direction.opposite())
+            .map(|dir| {
+                let next = snake.body[0].moved(dir);
+                let safe = self.is_free(next);
+                let distance = self
+                    .food
+                    .iter()
+                    .map(|food| (food.x - next.x).abs() + (food.y - next.y).abs())
+                    .min()
+                    .unwrap_or(0);
+                (dir, safe, distance)
+            })
+            .min_by_key(|(_, safe, distance)| (!safe, *distance))
+            .map(|(dir, _, _)| dir)
+    }
+}
+
+/// The count first, so the cells of one list don't run into the next.
+fn write_positions<'a>(hash: &mut Fnv1a, positions: impl ExactSizeIterator<Item = &'a Position>) {
+    hash.write_u32(positions.len() as u32);
+    for pos in positions {
+        hash.write(&pos.x.to_le_bytes());
+        hash.write(&pos.y.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PINNED: u64 = 0x503f_e6e6_98fe_8632;
+
+    /// Plays `ticks` ticks with a bot steering every snake, and returns the
+    /// board hash after each of them.
+    fn play(seed: u64, ticks: u32) -> Vec<u64> {
+        let mut board = Board::new(16, 16, 2, seed);
+        (0..ticks)
+            .map(|_| {
+                let inputs: Vec<_> = (0..2).map(|player| board.bot_direction(player)).collect();
+                board.step(&inputs);
+                board.hash()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_the_same_boards() {
+        assert_eq!(play(42, 500), play(42, 500));
+        assert_ne!(play(42, 500), play(43, 500));
+    }
+
+    #[test]
+    fn hash_is_stable_across_builds() {
+        let mut fnv = Fnv1a::default();
+        fnv.write(b"a");
+        assert_eq!(fnv.finish(), 0xaf63_dc4c_8601_ec8c);
+
+        // pinned, a different value means peers on older builds desync
+        let mut board = Board::new(16, 16, 2, 7);
+        board.food = vec![Position { x: 5, y: 5 }];
+        assert_eq!(board.hash(), PINNED);
+    }
+
+    #[test]
+    fn hash_changes_with_the_board() {
+        let mut board = Board::new(16, 16, 2, 7);
+        let before = board.hash();
+        board.step(&[Some(Direction::Right), None]);
+        assert_ne!(board.hash(), before);
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use rust_common::{
+    menu::not_paused,
+    stats::{unix_time, StatsLog},
+};
+use serde::Serialize;
+
+use crate::{
+    daily::DailyRun,
+    mode::{GameMode, ModeState},
+    DeathCause, GameOverEvent, GameRng, GrowthEvent, Score, SnakeMovement, SnakeSegments,
+};
+
+// there is a single kind of food so far
+const BASIC_FOOD: &str = "basic";
+
+/// One line of `snake_stats.jsonl`.
+#[derive(Serialize)]
+struct GameSummary {
+    finished_at: u64,
+    duration_secs: f64,
+    final_length: u32,
+    score: u32,
+    food_eaten: BTreeMap<&'static str, u32>,
+    cause_of_death: DeathCause,
+    mode: GameMode,
+    seed: u64,
+    daily: bool,
+}
+
+// Resource
+#[derive(Default)]
+struct CurrentGame {
+    started_at: f64,
+    food_eaten: BTreeMap<&'static str, u32>,
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(CurrentGame::default())
+            .insert_resource(StatsLog::new("snake"))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(not_paused.system())
+                    .with_system(count_food.system())
+                    .with_system(
+                        record_game
+                            .system()
+                            .after(SnakeMovement::Rules)
+                            .before(SnakeMovement::GameOver),
+                    ),
+            );
+    }
+}
+
+fn count_food(mut growth_reader: EventReader<GrowthEvent>, mut game: ResMut<CurrentGame>) {
+    for _ in growth_reader.iter() {
+        *game.food_eaten.entry(BASIC_FOOD).or_default() += 1;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_game(
+    mut reader: EventReader<GameOverEvent>,
+    mut game: ResMut<CurrentGame>,
+    log: Res<StatsLog>,
+    time: Res<Time>,
+    segments: Res<SnakeSegments>,
+    score: Res<Score>,
+    rng: Res<GameRng>,
+    mode: Res<ModeState>,
+    daily: Option<Res<DailyRun>>,
+) {
+    if let Some(GameOverEvent(cause)) = reader.iter().next() {
+        let now = time.seconds_since_startup();
+        let summary = GameSummary {
+            finished_at: unix_time(),
+            duration_secs: now - game.started_at,
+            final_length: segments.0.len() as u32,
+            score: score.0,
+            food_eaten: std::mem::take(&mut game.food_eaten),
+            cause_of_death: *cause,
+            mode: mode.mode,
+            seed: rng.seed,
+            daily: daily.is_some(),
+        };
+        if let Err(e) = log.append(&summary) {
+            warn!("could not write game stats: {}", e);
+        }
+        game.started_at = now;
+    }
+}